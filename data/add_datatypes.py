@@ -0,0 +1,56 @@
+import csv
+import json
+import sys
+
+# Wikibase ontology datatypes, as returned by the SPARQL query, mapped to their JSON dump names
+from convert_to_csv import DATATYPES
+
+PROPERTIES_FILES = ["./wikidata-en-properties.csv", "./wikidata-nl-properties.csv"]
+
+
+# Function to read the datatype of each property from the result of the datatype query
+def load_datatypes(json_file):
+    with open(json_file, "r", encoding="utf-8") as file:
+        json_data = json.load(file)
+    datatypes = {}
+    for binding in json_data["results"]["bindings"]:
+        key = binding["property"]["value"].replace("http://www.wikidata.org/entity/", "")
+        ontology_type = binding["datatype"]["value"].split("#")[-1]
+        if ontology_type in DATATYPES:
+            datatypes[key] = DATATYPES[ontology_type]
+    return datatypes
+
+
+# Function to set the datatype column of a properties file, without touching the other columns,
+# so the sentences and questions do not need to be generated again
+def add_datatypes(csv_file, datatypes):
+    with open(csv_file, "r", encoding="utf-8", newline="") as file:
+        lines = file.read().split("\n")
+    updated = []
+    for index, line in enumerate(lines):
+        if not line:
+            updated.append(line)
+            continue
+        fields = next(csv.reader([line], delimiter=";"))
+        if len(fields) > 4:
+            # Drop the existing datatype, which never contains a separator or quotes
+            line = line[: line.rindex(";")]
+        if index == 0:
+            updated.append(line + ";datatype")
+        elif fields[0] in datatypes:
+            updated.append(line + ";" + datatypes[fields[0]])
+        else:
+            updated.append(line)
+    with open(csv_file, "w", encoding="utf-8", newline="") as file:
+        file.write("\n".join(updated))
+
+
+# Main execution
+if __name__ == "__main__":
+    # Result of the datatype query, see README.md
+    json_file_path = sys.argv[1] if len(sys.argv) > 1 else "./wikidata-properties-datatypes.json"
+    datatypes = load_datatypes(json_file_path)
+    print(f"Loaded the datatypes of {len(datatypes)} properties")
+    for csv_file in PROPERTIES_FILES:
+        add_datatypes(csv_file, datatypes)
+        print(f"Updated {csv_file}")
@@ -8,6 +8,28 @@ from dotenv import load_dotenv
 load_dotenv()
 OLLAMA_URL = os.getenv("OLLAMA_URL", "http://localhost:11434/api/chat")
 
+# Wikibase ontology datatypes, as returned by the SPARQL query, mapped to their JSON dump names
+DATATYPES = {
+    "WikibaseItem": "wikibase-item",
+    "WikibaseProperty": "wikibase-property",
+    "WikibaseLexeme": "wikibase-lexeme",
+    "WikibaseForm": "wikibase-form",
+    "WikibaseSense": "wikibase-sense",
+    "ExternalId": "external-id",
+    "CommonsMedia": "commonsMedia",
+    "Url": "url",
+    "String": "string",
+    "Time": "time",
+    "Quantity": "quantity",
+    "GlobeCoordinate": "globe-coordinate",
+    "MonolingualText": "monolingualtext",
+    "Math": "math",
+    "MusicalNotation": "musical-notation",
+    "GeoShape": "geo-shape",
+    "TabularData": "tabular-data",
+    "EntitySchema": "entity-schema",
+}
+
 
 def generate_wikibase_prompt(value, language):
     """
@@ -112,7 +134,7 @@ def json_to_csv(json_data, output_file):
         csvwriter = csv.writer(csvfile, delimiter=";")
 
         # Write the header row to the CSV file
-        csvwriter.writerow(["key", "value", "sentence", "question"])
+        csvwriter.writerow(["key", "value", "sentence", "question", "datatype"])
 
         # Iterate over each binding in the JSON data
         for binding in json_data["results"]["bindings"]:
@@ -120,12 +142,15 @@ def json_to_csv(json_data, output_file):
             property_value = binding["property"]["value"]
             stripped_key = property_value.replace("http://www.wikidata.org/entity/", "")
             property_label_value = binding["propertyLabel"]["value"]
+            # Only present when the query also selects ?datatype
+            ontology_type = binding.get("datatype", {}).get("value", "").split("#")[-1]
+            datatype = DATATYPES.get(ontology_type, "")
 
             payload = generate_wikibase_prompt(property_label_value, language)
             sentence, question = call_ollama(payload)
             # Write a row to the CSV file
             print(f"{stripped_key}: {property_label_value}, {sentence}, {question}")
-            csvwriter.writerow([stripped_key, property_label_value, sentence, question])
+            csvwriter.writerow([stripped_key, property_label_value, sentence, question, datatype])
 
 
 # Main execution
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use crate::csv_writer_pool::CsvWriterPool;
+use crate::entity::RenderedEntity;
+
+/// Directory, next to the language directories, containing the aligned output
+pub const ALIGNED_DIR: &str = "aligned";
+
+/// Column names of the aligned output: the QID and property, followed by the sentence and
+/// question in each language
+pub fn aligned_header(langs: &[String]) -> Vec<String> {
+    let mut header = vec!["id".to_string(), "property".to_string()];
+    for lang in langs {
+        header.push(format!("sentence_{}", lang));
+        header.push(format!("question_{}", lang));
+    }
+    header
+}
+
+/// Write the claims of an entity that are rendered in at least two languages side by side, one
+/// row per property. The entity is rendered once per language, in the order of the header, and
+/// is written to the file of the category of the first language it is rendered in.
+pub fn write_aligned(csv_writers: &CsvWriterPool, id: &str, rendered: &[Option<RenderedEntity>]) {
+    let Some(category) = rendered.iter().flatten().map(|r| &r.category).next() else {
+        return;
+    };
+
+    // Properties in order of appearance, with their sentence and question in each language
+    let mut rows: Vec<(&str, Vec<(&str, &str)>)> = Vec::new();
+    let mut row_index: HashMap<&str, usize> = HashMap::new();
+    for (lang_index, entity) in rendered.iter().enumerate() {
+        for claim in entity.iter().flat_map(|entity| &entity.claims) {
+            let index = *row_index.entry(claim.key).or_insert_with(|| {
+                rows.push((claim.key, vec![("", ""); rendered.len()]));
+                rows.len() - 1
+            });
+            rows[index].1[lang_index] = (
+                claim.sentence.as_deref().unwrap_or(""),
+                claim.question.as_deref().unwrap_or(""),
+            );
+        }
+    }
+
+    for (key, texts) in rows {
+        let languages = texts
+            .iter()
+            .filter(|(sentence, question)| !sentence.is_empty() || !question.is_empty())
+            .count();
+        if languages < 2 {
+            continue;
+        }
+        let mut record = vec![id, key];
+        for (sentence, question) in texts {
+            record.push(sentence);
+            record.push(question);
+        }
+        csv_writers.write(category, &record);
+    }
+}
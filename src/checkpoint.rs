@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{remove_file, rename, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::processing_error::ProcessingError;
+
+/// Progress of a run, from which it can be resumed after a crash or reboot
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Path of the Wikidata dump that is processed
+    pub input_file: String,
+    /// Languages that are generated
+    #[serde(default)]
+    pub langs: Vec<String>,
+    /// Whether aligned output is generated
+    #[serde(default)]
+    pub aligned: bool,
+    /// Offset in the (decompressed) dump below which every line has been processed
+    pub offset: u64,
+    /// Length of each output file at the time of the checkpoint
+    pub files: HashMap<String, u64>,
+}
+
+impl Checkpoint {
+    fn path(output_dir: &str) -> PathBuf {
+        Path::new(output_dir).join("checkpoint.json")
+    }
+
+    /// Load the last checkpoint from the output directory, if any
+    pub fn load(output_dir: &str) -> Result<Option<Self>, ProcessingError> {
+        let path = Self::path(output_dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let checkpoint = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        Ok(Some(checkpoint))
+    }
+
+    /// Save the checkpoint, replacing the previous one only after it is completely written
+    pub fn save(&self, output_dir: &str) -> Result<(), ProcessingError> {
+        let path = Self::path(output_dir);
+        let temp_path = path.with_extension("json.tmp");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        rename(temp_path, path)?;
+        Ok(())
+    }
+
+    /// Remove the checkpoint after a completed run
+    pub fn remove(output_dir: &str) -> Result<(), ProcessingError> {
+        let path = Self::path(output_dir);
+        if path.exists() {
+            remove_file(path)?;
+        }
+        Ok(())
+    }
+}
@@ -1,17 +1,99 @@
-use clap::{Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, Command, ValueEnum};
 use std::fs::create_dir_all;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::processing_error::ProcessingError;
 
+/// Statements to render, based on their rank
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RankFilter {
+    /// All statements, including deprecated ones
+    All,
+    /// Preferred and normal statements, skipping deprecated ones
+    Normal,
+    /// Only the preferred statements of a property when it has any, else the normal ones
+    Preferred,
+}
+
+/// System of units that quantities are converted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum UnitSystem {
+    /// Keep the unit of each quantity
+    Original,
+    /// Convert imperial units to metric ones, e.g. feet to metres
+    Metric,
+    /// Convert metric units to imperial ones, e.g. metres to feet
+    Imperial,
+}
+
+/// Notation of geographic coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CoordinateFormat {
+    /// Degrees, minutes and seconds, e.g. 52°22'22.0"N, 4°53'35.8"E
+    Dms,
+    /// Decimal degrees, e.g. 52.3728°N, 4.8933°E
+    Decimal,
+    /// Decimal degrees rounded to about a kilometre, e.g. 52.37°N 4.89°E
+    Short,
+}
+
+/// Handling of monolingual texts in other languages than the rendered ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MonolingualFilter {
+    /// Leave them out
+    Drop,
+    /// Keep them, mentioning their language, e.g. (in Latin: Fluctuat nec mergitur)
+    Tag,
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
-    /// Preferred language for the results
-    pub lang: String,
+    /// Languages of the results, each rendered to its own output directory
+    pub langs: Vec<String>,
+    /// Languages to fall back to, in order, when a label, description or alias is missing
+    pub fallback: Vec<String>,
     /// Output directory, will be created automatically if it doesn't exist
     pub output_dir: String,
     /// Recreate the cache if it exists. If it doesn't exist, it will be created.
     pub recreate_cache: bool,
+    /// Scan the dump only once, resolving the labels afterwards using an intermediate file
+    pub single_pass: bool,
+    /// Statements to render, based on their rank
+    pub rank: RankFilter,
+    /// System of units that quantities are converted to
+    pub unit_system: UnitSystem,
+    /// Notation of geographic coordinates
+    pub coordinate_format: CoordinateFormat,
+    /// Handling of monolingual texts in other languages than the rendered ones
+    pub monolingual: MonolingualFilter,
+    /// Refer to men and women by their pronoun after the first sentence
+    pub pronouns: bool,
+    /// Seed for picking among the variants of a template, together with the entity id
+    pub seed: u64,
+    /// Also write the claims rendered in each language side by side
+    pub aligned: bool,
+    /// Resume processing from the last checkpoint, appending to the existing output
+    pub resume: bool,
+    /// Minimum number of seconds between two checkpoints
+    pub checkpoint_interval: u64,
+}
+
+impl Config {
+    /// The given language followed by the fallback languages, without duplicates
+    pub fn languages(&self, lang: &str) -> Vec<String> {
+        let mut languages = vec![lang.to_string()];
+        for lang in &self.fallback {
+            if !languages.contains(lang) {
+                languages.push(lang.clone());
+            }
+        }
+        languages
+    }
+
+    /// Path of the class cache, which is shared by all languages
+    pub fn class_cache_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}/class_cache.bin", self.output_dir))
+    }
 }
 
 /// Get the input file and additional configuration settings
@@ -23,8 +105,13 @@ pub fn get_configuration() -> Result<(String, Config), ProcessingError> {
       .arg(Arg::new("lang")
           .short('l')
           .long("lang")
-          .help("Language for labels and descriptions")
+          .help("Comma separated languages for labels and descriptions, e.g. en,nl, all generated in one pass")
           .default_value("en"))
+      .arg(Arg::new("fallback")
+          .short('f')
+          .long("fallback")
+          .help("Comma separated languages to use, in order, when a label, description or alias is missing in the main language, e.g. mul,en")
+          .default_value(""))
       .arg(Arg::new("output_dir")
           .short('o')
           .long("output")
@@ -40,23 +127,119 @@ pub fn get_configuration() -> Result<(String, Config), ProcessingError> {
           .help("Recreate cache from scratch: in case no cache file is found, it will also be true.")
           .action(ArgAction::SetTrue) // This makes it a flag, not requiring a value
           .default_value("false"))
+      .arg(Arg::new("single_pass")
+          .short('s')
+          .long("single_pass")
+          .help("Scan the dump only once when (re)creating the cache, resolving labels afterwards using an intermediate file.")
+          .action(ArgAction::SetTrue)
+          .default_value("false"))
+      .arg(Arg::new("rank")
+          .long("rank")
+          .help("Statements to render: all, normal (skip deprecated ones) or preferred (only the preferred ones of a property when it has any)")
+          .value_parser(clap::value_parser!(RankFilter))
+          .default_value("normal"))
+      .arg(Arg::new("unit_system")
+          .long("unit_system")
+          .help("Convert quantities to the metric or imperial system, or keep their original unit")
+          .value_parser(clap::value_parser!(UnitSystem))
+          .default_value("original"))
+      .arg(Arg::new("coordinate_format")
+          .long("coordinate_format")
+          .help("Notation of coordinates: dms (degrees, minutes and seconds), decimal (degrees) or short (decimal degrees rounded to about a kilometre)")
+          .value_parser(clap::value_parser!(CoordinateFormat))
+          .default_value("dms"))
+      .arg(Arg::new("monolingual")
+          .long("monolingual")
+          .help("Monolingual texts, e.g. native names or mottos, in other languages than the main and fallback languages: drop them, or tag them with their language")
+          .value_parser(clap::value_parser!(MonolingualFilter))
+          .default_value("drop"))
+      .arg(Arg::new("pronouns")
+          .long("pronouns")
+          .help("Start the follow-up sentences about a man or woman with a pronoun, e.g. She was born in..., instead of the name.")
+          .action(ArgAction::SetTrue)
+          .default_value("false"))
+      .arg(Arg::new("seed")
+          .long("seed")
+          .help("Seed for picking among the variants of a template when the properties file has several rows for a property. The same seed always renders an entity the same way.")
+          .value_parser(clap::value_parser!(u64))
+          .default_value("0"))
+      .arg(Arg::new("aligned")
+          .short('a')
+          .long("aligned")
+          .help("Also write the claims rendered in each language side by side, keyed by QID and property, to the aligned output directory.")
+          .action(ArgAction::SetTrue)
+          .default_value("false"))
+      .arg(Arg::new("resume")
+          .long("resume")
+          .help("Resume from the last checkpoint, appending to the existing output files.")
+          .action(ArgAction::SetTrue)
+          .default_value("false"))
+      .arg(Arg::new("checkpoint_interval")
+          .long("checkpoint_interval")
+          .help("Minimum number of seconds between two checkpoints")
+          .value_parser(clap::value_parser!(u64))
+          .default_value("300"))
       .get_matches();
-    let lang = matches.get_one::<String>("lang").unwrap().to_string();
+    let langs: Vec<String> = matches
+        .get_one::<String>("lang")
+        .unwrap()
+        .split(',')
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect();
+    if langs.is_empty() {
+        return Err(ProcessingError::Other("No language specified".to_string()));
+    }
+    let fallback = matches
+        .get_one::<String>("fallback")
+        .unwrap()
+        .split(',')
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect();
     let output_dir = matches
         .get_one::<String>("output_dir")
         .unwrap()
         .trim()
         .to_string();
     let recreate_cache = matches.get_flag("recreate_cache");
+    let single_pass = matches.get_flag("single_pass");
+    let rank = *matches.get_one::<RankFilter>("rank").unwrap();
+    let unit_system = *matches.get_one::<UnitSystem>("unit_system").unwrap();
+    let coordinate_format = *matches
+        .get_one::<CoordinateFormat>("coordinate_format")
+        .unwrap();
+    let monolingual = *matches.get_one::<MonolingualFilter>("monolingual").unwrap();
+    let pronouns = matches.get_flag("pronouns");
+    let seed = *matches.get_one::<u64>("seed").unwrap();
+    let aligned = matches.get_flag("aligned");
+    if aligned && langs.len() < 2 {
+        return Err(ProcessingError::Other(
+            "Aligned output requires at least two languages".to_string(),
+        ));
+    }
+    let resume = matches.get_flag("resume");
+    let checkpoint_interval = *matches.get_one::<u64>("checkpoint_interval").unwrap();
     let output_path = Path::new(&output_dir);
     if !output_path.exists() {
         create_dir_all(output_path)?;
     }
     let input_file = matches.get_one::<String>("input_file").unwrap().to_string();
     let config = Config {
-        lang,
+        langs,
+        fallback,
         output_dir,
         recreate_cache,
+        single_pass,
+        rank,
+        unit_system,
+        coordinate_format,
+        monolingual,
+        pronouns,
+        seed,
+        aligned,
+        resume,
+        checkpoint_interval,
     };
     Ok((input_file, config))
 }
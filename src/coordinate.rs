@@ -0,0 +1,185 @@
+use serde_json::Value;
+
+use crate::config::CoordinateFormat;
+use crate::entity::{ClaimValue, ENTITY_URL_PREFIX};
+use crate::locale::Locale;
+
+/// Globe of coordinates on Earth (Q2), which is left out of the text
+const EARTH: u32 = 2;
+/// Precision in degrees of coordinates without one, i.e. an arcsecond
+const DEFAULT_PRECISION: f64 = 1.0 / 3600.0;
+/// Maximum number of decimals of degrees, about 10 cm
+const MAX_DEGREE_DECIMALS: usize = 6;
+/// Maximum number of decimals of seconds
+const MAX_SECOND_DECIMALS: usize = 2;
+/// Number of decimals of degrees in the short format, about a kilometre
+const SHORT_DECIMALS: usize = 2;
+
+/// Number of decimals needed to show a value with the given precision, e.g. 4 for 0.0001
+fn decimals(precision: f64, max: usize) -> usize {
+    // Ignore floating point noise, e.g. 4.0000000001 for 0.0001
+    (-precision.log10() - 1e-6).ceil().clamp(0.0, max as f64) as usize
+}
+
+/// Compass point of a latitude or longitude, e.g. N or S, from the locale
+fn compass_point(locale: &Locale, value: f64, is_latitude: bool) -> &str {
+    let index = match (is_latitude, value >= 0.0) {
+        (true, true) => 0,
+        (true, false) => 1,
+        (false, true) => 2,
+        (false, false) => 3,
+    };
+    locale.compass_points.get(index).map_or("", String::as_str)
+}
+
+/// Degrees, minutes and seconds, e.g. 52°22'22"N, leaving out the minutes or seconds when the
+/// precision is too low to show them. The value is rounded as a whole, so 59.9 seconds becomes
+/// the next minute instead of 60 seconds.
+fn format_dms(value: f64, precision: f64, point: &str) -> String {
+    let value = value.abs();
+    if precision >= 1.0 {
+        return format!("{}°{}", value.round(), point);
+    }
+    if precision >= 1.0 / 60.0 {
+        let minutes = (value * 60.0).round() as u64;
+        return format!("{}°{}'{}", minutes / 60, minutes % 60, point);
+    }
+    let decimals = decimals(precision * 3600.0, MAX_SECOND_DECIMALS);
+    let scale = 10u64.pow(decimals as u32);
+    let units = (value * 3600.0 * scale as f64).round() as u64;
+    let degrees = units / (3600 * scale);
+    let minutes = units / (60 * scale) % 60;
+    let seconds = (units % (60 * scale)) as f64 / scale as f64;
+    format!(
+        "{}°{}'{:.*}\"{}",
+        degrees, minutes, decimals, seconds, point
+    )
+}
+
+/// Decimal degrees, e.g. 52.3728°N
+fn format_decimal(value: f64, decimals: usize, point: &str) -> String {
+    format!("{:.*}°{}", decimals, value.abs(), point)
+}
+
+/// Convert the value of a globecoordinate datavalue to a claim value, formatted according to its
+/// precision. Coordinates on other globes than Earth, e.g. a crater on the Moon, keep a reference
+/// to their globe, so the text can mention it.
+pub fn extract_coordinate(
+    value: &Value,
+    locale: &Locale,
+    format: CoordinateFormat,
+) -> Option<ClaimValue> {
+    let latitude = value["latitude"].as_f64()?;
+    let longitude = value["longitude"].as_f64()?;
+    let precision = value["precision"]
+        .as_f64()
+        .filter(|precision| *precision > 0.0)
+        .unwrap_or(DEFAULT_PRECISION);
+    let points = (
+        compass_point(locale, latitude, true),
+        compass_point(locale, longitude, false),
+    );
+
+    let text = match format {
+        CoordinateFormat::Dms => format!(
+            "{}, {}",
+            format_dms(latitude, precision, points.0),
+            format_dms(longitude, precision, points.1)
+        ),
+        CoordinateFormat::Decimal => {
+            let decimals = decimals(precision, MAX_DEGREE_DECIMALS);
+            format!(
+                "{}, {}",
+                format_decimal(latitude, decimals, points.0),
+                format_decimal(longitude, decimals, points.1)
+            )
+        }
+        CoordinateFormat::Short => {
+            let decimals = decimals(precision, SHORT_DECIMALS);
+            format!(
+                "{} {}",
+                format_decimal(latitude, decimals, points.0),
+                format_decimal(longitude, decimals, points.1)
+            )
+        }
+    };
+    // The short format is meant for places, where the altitude is of little use
+    let text = match value["altitude"].as_f64() {
+        Some(altitude) if format != CoordinateFormat::Short => {
+            format!("{} ({}m)", text, altitude.round())
+        }
+        _ => text,
+    };
+
+    let globe = value["globe"]
+        .as_str()
+        .and_then(|globe| globe.strip_prefix(ENTITY_URL_PREFIX)?.parse::<u32>().ok())
+        .unwrap_or(EARTH);
+    Some(if globe == EARTH {
+        ClaimValue::Text(text)
+    } else {
+        ClaimValue::Coordinate { text, globe }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dashmap::DashMap;
+    use serde_json::json;
+
+    #[test]
+    fn rounds_seconds_into_the_next_minute() {
+        let value = 12.0 + 30.0 / 60.0 + 59.99 / 3600.0;
+        assert_eq!(format_dms(value, DEFAULT_PRECISION, "N"), "12°31'0\"N");
+        assert_eq!(format_dms(value, 0.01 / 3600.0, "N"), "12°30'59.99\"N");
+        assert_eq!(
+            format_dms(12.0 + 59.0 / 60.0 + 59.999 / 3600.0, 0.1 / 3600.0, "N"),
+            "13°0'0.0\"N"
+        );
+    }
+
+    #[test]
+    fn leaves_out_minutes_and_seconds_beyond_the_precision() {
+        assert_eq!(format_dms(-52.6, 1.0, "S"), "53°S");
+        assert_eq!(format_dms(52.3728, 1.0 / 60.0, "N"), "52°22'N");
+    }
+
+    #[test]
+    fn formats_coordinates() {
+        let locale = Locale::load("en", &DashMap::new());
+        let amsterdam = json!({
+            "latitude": 52.372777777778,
+            "longitude": 4.8933333333333,
+            "precision": 0.0001,
+            "globe": "http://www.wikidata.org/entity/Q2",
+        });
+        let text = |value: &Value, format: CoordinateFormat| match extract_coordinate(
+            value, &locale, format,
+        )
+        .unwrap()
+        {
+            ClaimValue::Text(text) => text,
+            value => panic!("Unexpected claim value {:?}", value),
+        };
+        assert_eq!(
+            text(&amsterdam, CoordinateFormat::Decimal),
+            "52.3728°N, 4.8933°E"
+        );
+        assert_eq!(text(&amsterdam, CoordinateFormat::Short), "52.37°N 4.89°E");
+
+        let tycho = json!({
+            "latitude": -43.3,
+            "longitude": -11.2,
+            "precision": 0.1,
+            "globe": "http://www.wikidata.org/entity/Q405",
+        });
+        match extract_coordinate(&tycho, &locale, CoordinateFormat::Dms).unwrap() {
+            ClaimValue::Coordinate { text, globe } => {
+                assert_eq!(text, "43°18'S, 11°12'W");
+                assert_eq!(globe, 405);
+            }
+            value => panic!("Unexpected claim value {:?}", value),
+        }
+    }
+}
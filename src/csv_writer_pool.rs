@@ -1,7 +1,8 @@
 use csv::{Writer, WriterBuilder};
 use dashmap::DashMap;
 use regex::Regex;
-use std::fs::{create_dir_all, File};
+use std::collections::{HashMap, HashSet};
+use std::fs::{create_dir_all, File, OpenOptions};
 use std::io::BufWriter;
 use std::sync::{Arc, RwLock};
 
@@ -9,6 +10,10 @@ use std::sync::{Arc, RwLock};
 pub struct CsvWriterPool {
     writers: DashMap<String, Arc<RwLock<Writer<BufWriter<File>>>>>,
     output_dir: String,
+    /// Column names, written at the start of each new file
+    header: Vec<String>,
+    /// Existing files of a resumed run, which are appended to instead of truncated
+    append_paths: HashSet<String>,
 }
 
 fn sanitize_filename(category: &str) -> String {
@@ -19,16 +24,37 @@ fn sanitize_filename(category: &str) -> String {
 }
 
 impl CsvWriterPool {
-    /// Creates a new CSV writer pool with the given output directory
-    pub fn new(output_dir: &str) -> Self {
+    /// Creates a new CSV writer pool with the given output directory and column names
+    pub fn new<S: AsRef<str>>(output_dir: &str, header: &[S]) -> Self {
         println!("Creating output directory: {}", output_dir);
         create_dir_all(output_dir).expect("Failed to create output directory");
         Self {
             writers: DashMap::new(),
             output_dir: output_dir.to_string(),
+            header: header
+                .iter()
+                .map(|column| column.as_ref().to_string())
+                .collect(),
+            append_paths: HashSet::new(),
         }
     }
 
+    /// Creates a CSV writer pool that continues a previous run. The files of that run are
+    /// truncated to their length at the checkpoint, and appended to from thereon.
+    pub fn resume<S: AsRef<str>>(
+        output_dir: &str,
+        header: &[S],
+        files: &HashMap<String, u64>,
+    ) -> std::io::Result<Self> {
+        let mut pool = Self::new(output_dir, header);
+        let prefix = format!("{}/", output_dir);
+        for (path, len) in files.iter().filter(|(path, _)| path.starts_with(&prefix)) {
+            OpenOptions::new().write(true).open(path)?.set_len(*len)?;
+            pool.append_paths.insert(path.clone());
+        }
+        Ok(pool)
+    }
+
     /// Writes a record to the appropriate CSV file
     pub fn write(&self, category: &str, record: &[&str]) {
         if record.iter().all(|&field| field.is_empty()) {
@@ -41,16 +67,26 @@ impl CsvWriterPool {
             .entry(filename.to_string())
             .or_insert_with(|| {
                 let path = format!("{}/{}.csv", self.output_dir, filename);
-                let file = File::create(&path).expect("Failed to create CSV file");
+                let append = self.append_paths.contains(&path);
+                let file = if append {
+                    OpenOptions::new()
+                        .append(true)
+                        .open(&path)
+                        .expect("Failed to open CSV file")
+                } else {
+                    File::create(&path).expect("Failed to create CSV file")
+                };
 
                 // Use WriterBuilder to set semi-colon delimiter
                 let mut writer = WriterBuilder::new()
                     .delimiter(b';') // Set semi-colon as delimiter
                     .quote_style(csv::QuoteStyle::Necessary)
                     .from_writer(BufWriter::new(file));
-                writer
-                    .write_record(&["label", "sentences", "questions"])
-                    .expect("Failed to write header");
+                if !append {
+                    writer
+                        .write_record(&self.header)
+                        .expect("Failed to write header");
+                }
                 Arc::new(RwLock::new(writer))
             })
             .clone();
@@ -73,4 +109,16 @@ impl CsvWriterPool {
             writer_lock.flush().expect("Failed to flush CSV file");
         });
     }
+
+    /// Flushes all writers to disk, and returns the length of each file
+    pub fn flush_and_measure(&self) -> std::io::Result<HashMap<String, u64>> {
+        let mut files = HashMap::new();
+        for entry in self.writers.iter() {
+            let mut writer_lock = entry.value().write().unwrap();
+            writer_lock.flush()?;
+            let len = writer_lock.get_ref().get_ref().metadata()?.len();
+            files.insert(format!("{}/{}.csv", self.output_dir, entry.key()), len);
+        }
+        Ok(files)
+    }
 }
@@ -0,0 +1,451 @@
+use bzip2::read::{BzDecoder, MultiBzDecoder};
+use flate2::read::MultiGzDecoder;
+use memmap2::{Mmap, MmapOptions};
+use rayon::iter::{IntoParallelRefIterator, ParallelBridge, ParallelIterator};
+use std::fs::File;
+use std::io::{BufReader, Cursor, ErrorKind, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::processing_error::ProcessingError;
+use crate::shutdown::check_shutdown;
+
+/// Magic bytes of a bzip2 block, directly following the `BZh[1-9]` stream header
+const BZIP2_BLOCK_MAGIC: [u8; 6] = [0x31, 0x41, 0x59, 0x26, 0x53, 0x59];
+
+/// Compression format of the Wikidata dump, derived from its file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+}
+
+impl Compression {
+    pub fn from_path(path: &str) -> Self {
+        if path.ends_with(".gz") {
+            Compression::Gzip
+        } else if path.ends_with(".bz2") {
+            Compression::Bzip2
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Reader that keeps track of the number of bytes read from the (compressed) source
+struct CountingReader<R> {
+    inner: R,
+    consumed: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.consumed.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+/// Error for a background thread that stopped without sending the end of its data, e.g. after a
+/// panic, so the dump is not silently cut short
+fn stopped_unexpectedly(thread: &str) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::UnexpectedEof,
+        format!("The {} thread stopped before the end of the dump", thread),
+    )
+}
+
+/// Decoded stream, with the offset of its end in the compressed file
+type DecodedStream = (Vec<u8>, u64);
+
+/// Decompresses a multistream bzip2 file, decoding batches of streams in parallel.
+/// The decoded streams are handed out in their original order, followed by `None`.
+struct ParallelBzReader {
+    receiver: Receiver<std::io::Result<Option<DecodedStream>>>,
+    current: Cursor<Vec<u8>>,
+    consumed: Arc<AtomicU64>,
+    finished: bool,
+}
+
+impl ParallelBzReader {
+    fn new(mmap: Arc<Mmap>, streams: Vec<(usize, usize)>, consumed: Arc<AtomicU64>) -> Self {
+        let threads = thread::available_parallelism().map_or(4, |n| n.get());
+        let (sender, receiver) = sync_channel(2 * threads);
+        thread::spawn(move || {
+            for batch in streams.chunks(threads) {
+                let decoded: Vec<std::io::Result<Vec<u8>>> = thread::scope(|scope| {
+                    let handles: Vec<_> = batch
+                        .iter()
+                        .map(|&(start, end)| {
+                            let data = &mmap[start..end];
+                            scope.spawn(move || {
+                                let mut buffer = Vec::with_capacity(4 * data.len());
+                                BzDecoder::new(data).read_to_end(&mut buffer)?;
+                                Ok(buffer)
+                            })
+                        })
+                        .collect();
+                    handles
+                        .into_iter()
+                        .map(|h| {
+                            h.join()
+                                .unwrap_or_else(|_| Err(stopped_unexpectedly("bzip2 decoder")))
+                        })
+                        .collect()
+                });
+                for (result, &(_, end)) in decoded.into_iter().zip(batch) {
+                    let failed = result.is_err();
+                    if sender
+                        .send(result.map(|data| Some((data, end as u64))))
+                        .is_err()
+                        || failed
+                    {
+                        return;
+                    }
+                }
+            }
+            sender.send(Ok(None)).ok();
+        });
+        Self {
+            receiver,
+            current: Cursor::new(Vec::new()),
+            consumed,
+            finished: false,
+        }
+    }
+}
+
+impl Read for ParallelBzReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            let n = self.current.read(buf)?;
+            if n > 0 || buf.is_empty() || self.finished {
+                return Ok(n);
+            }
+            match self.receiver.recv() {
+                Ok(Ok(Some((data, end)))) => {
+                    self.consumed.store(end, Ordering::Relaxed);
+                    self.current = Cursor::new(data);
+                }
+                Ok(Ok(None)) => self.finished = true, // All streams are decoded
+                Ok(Err(e)) => return Err(e),
+                Err(_) => return Err(stopped_unexpectedly("bzip2 decoder")),
+            }
+        }
+    }
+}
+
+/// Find the byte ranges of the individual streams in a (multistream) bzip2 file.
+fn find_bzip2_streams(data: &[u8]) -> Vec<(usize, usize)> {
+    let mut starts: Vec<usize> = memchr::memmem::find_iter(data, &BZIP2_BLOCK_MAGIC)
+        .filter(|&pos| {
+            pos >= 4 && &data[pos - 4..pos - 1] == b"BZh" && (b'1'..=b'9').contains(&data[pos - 1])
+        })
+        .map(|pos| pos - 4)
+        .collect();
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| (start, starts.get(i + 1).copied().unwrap_or(data.len())))
+        .collect()
+}
+
+/// Target size of the blocks of lines that are handed to the worker threads
+const CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Source of a plain or compressed Wikidata dump, tracking how many bytes
+/// of the file on disk have been consumed.
+pub struct DumpReader {
+    source: DumpSource,
+    consumed: Arc<AtomicU64>,
+    size: u64,
+}
+
+enum DumpSource {
+    /// Uncompressed dump, which is split into chunks without copying
+    Mapped(Mmap),
+    /// Decompressed stream, which is read into chunks by a background thread
+    Stream(Box<dyn Read + Send>),
+}
+
+impl DumpReader {
+    /// Opens the dump, decompressing `.json.gz` and `.json.bz2` files as a stream
+    pub fn open(input_path: &str) -> Result<Self, ProcessingError> {
+        let file = File::open(input_path)?;
+        let size = file.metadata()?.len();
+        let consumed = Arc::new(AtomicU64::new(0));
+        let counting_file = |file: File| {
+            BufReader::new(CountingReader {
+                inner: file,
+                consumed: consumed.clone(),
+            })
+        };
+        let source = match Compression::from_path(input_path) {
+            Compression::None => DumpSource::Mapped(unsafe { MmapOptions::new().map(&file)? }),
+            Compression::Gzip => {
+                DumpSource::Stream(Box::new(MultiGzDecoder::new(counting_file(file))))
+            }
+            Compression::Bzip2 => {
+                let mmap = Arc::new(unsafe { MmapOptions::new().map(&file)? });
+                let streams = find_bzip2_streams(&mmap);
+                if streams.len() > 1 {
+                    println!("Decoding {} bzip2 streams in parallel", streams.len());
+                    DumpSource::Stream(Box::new(ParallelBzReader::new(
+                        mmap,
+                        streams,
+                        consumed.clone(),
+                    )))
+                } else {
+                    DumpSource::Stream(Box::new(MultiBzDecoder::new(counting_file(file))))
+                }
+            }
+        };
+        Ok(Self {
+            source,
+            consumed,
+            size,
+        })
+    }
+}
+
+/// Split the data into byte ranges of at most about `CHUNK_SIZE`, each ending directly after
+/// a newline. Smaller files use smaller chunks, so all worker threads get their share.
+fn chunk_ranges(data: &[u8]) -> Vec<(usize, usize)> {
+    let chunk_size = (data.len() / (4 * rayon::current_num_threads())).clamp(1 << 20, CHUNK_SIZE);
+    let mut ranges = Vec::with_capacity(data.len() / chunk_size + 1);
+    let mut start = 0;
+    while start < data.len() {
+        let end = (start + chunk_size).min(data.len());
+        let end = memchr::memchr(b'\n', &data[end..]).map_or(data.len(), |pos| end + pos + 1);
+        ranges.push((start, end));
+        start = end;
+    }
+    ranges
+}
+
+/// Chunks read by `spawn_chunk_reader`, ending when the reader thread sends `None`
+struct Chunks {
+    receiver: Receiver<std::io::Result<Option<Vec<u8>>>>,
+    finished: bool,
+}
+
+impl Iterator for Chunks {
+    type Item = std::io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.receiver.recv() {
+            Ok(Ok(Some(chunk))) => Some(Ok(chunk)),
+            Ok(Ok(None)) => {
+                self.finished = true;
+                None
+            }
+            Ok(Err(e)) => Some(Err(e)),
+            Err(_) => {
+                self.finished = true;
+                Some(Err(stopped_unexpectedly("dump reader")))
+            }
+        }
+    }
+}
+
+/// Read the stream into chunks of about `CHUNK_SIZE` on a background thread.
+/// Each chunk ends directly after a newline, except for the last one.
+fn spawn_chunk_reader(mut reader: Box<dyn Read + Send>) -> Chunks {
+    let threads = thread::available_parallelism().map_or(4, |n| n.get());
+    let (sender, receiver) = sync_channel(threads);
+    thread::spawn(move || {
+        let mut carry = Vec::new();
+        loop {
+            let mut chunk = std::mem::take(&mut carry);
+            chunk.reserve(CHUNK_SIZE);
+            match reader
+                .by_ref()
+                .take(CHUNK_SIZE as u64)
+                .read_to_end(&mut chunk)
+            {
+                Ok(0) if chunk.is_empty() => {
+                    sender.send(Ok(None)).ok();
+                    return;
+                }
+                Ok(n) => {
+                    if n > 0 {
+                        if let Some(pos) = memchr::memrchr(b'\n', &chunk) {
+                            carry = chunk.split_off(pos + 1);
+                        } else {
+                            // No complete line yet: keep on reading
+                            carry = chunk;
+                            continue;
+                        }
+                    }
+                    if sender.send(Ok(Some(chunk))).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    sender.send(Err(e)).ok();
+                    return;
+                }
+            }
+        }
+    });
+    Chunks {
+        receiver,
+        finished: false,
+    }
+}
+
+/// Call `process_line` for each entity line in the chunk, without copying the data
+fn process_chunk<F>(chunk: &[u8], process_line: &F) -> Result<(), ProcessingError>
+where
+    F: Fn(&str) -> Result<(), ProcessingError>,
+{
+    for line in chunk.split(|&b| b == b'\n') {
+        check_shutdown()?;
+        let line = std::str::from_utf8(line)
+            .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?
+            .trim_end_matches('\r');
+
+        if line.trim().is_empty() || line.starts_with(['[', ']']) {
+            continue;
+        }
+
+        process_line(line.trim_end_matches(','))?;
+    }
+    Ok(())
+}
+
+// Extracted progress printing for reusability
+fn print_progress(start_time: Instant, current_promille: u64) {
+    let elapsed = start_time.elapsed();
+    let eta = if current_promille > 0 {
+        let total_estimated_time = elapsed.as_secs_f64() / (current_promille as f64 / 1000.0);
+        Duration::from_secs_f64(total_estimated_time - elapsed.as_secs_f64())
+    } else {
+        Duration::from_secs(0)
+    };
+
+    print!(
+        "\rProcessing: {:.1}% | Elapsed: {:.0}s | ETA: {:.0}s         ",
+        current_promille as f64 / 10.0,
+        elapsed.as_secs(),
+        eta.as_secs()
+    );
+    std::io::stdout().flush().ok();
+}
+
+/// Process every entity line of the dump in parallel, reporting progress on the way.
+/// The callback receives the JSON of a single entity, without the trailing comma.
+pub fn for_each_line<F>(input_path: &str, process_line: F) -> Result<(), ProcessingError>
+where
+    F: Fn(&str) -> Result<(), ProcessingError> + Send + Sync,
+{
+    for_each_line_from(input_path, 0, process_line, |_| Ok(()))
+}
+
+/// Process every entity line of the dump in parallel, starting at the given offset in the
+/// (decompressed) dump. Lines are processed in batches of chunks: after each batch, all lines
+/// before the offset passed to `checkpoint` have been processed.
+pub fn for_each_line_from<F, C>(
+    input_path: &str,
+    start_offset: u64,
+    process_line: F,
+    mut checkpoint: C,
+) -> Result<(), ProcessingError>
+where
+    F: Fn(&str) -> Result<(), ProcessingError> + Send + Sync,
+    C: FnMut(u64) -> Result<(), ProcessingError>,
+{
+    let dump = DumpReader::open(input_path)?;
+    let file_size = dump.size.max(1);
+    let consumed = dump.consumed;
+    let batch_size = 8 * rayon::current_num_threads();
+
+    // Progress tracking
+    let start_time = Instant::now();
+    let last_reported_promille = AtomicU64::new(0);
+    let report_progress = || {
+        let current_promille = consumed.load(Ordering::Relaxed) * 1000 / file_size;
+        if current_promille > last_reported_promille.load(Ordering::Relaxed) {
+            last_reported_promille.store(current_promille, Ordering::Relaxed);
+            print_progress(start_time, current_promille);
+        }
+    };
+
+    match dump.source {
+        DumpSource::Mapped(mmap) => {
+            let start_offset = (start_offset as usize).min(mmap.len());
+            consumed.store(start_offset as u64, Ordering::Relaxed);
+            let ranges = chunk_ranges(&mmap[start_offset..]);
+            for batch in ranges.chunks(batch_size) {
+                batch
+                    .par_iter()
+                    .try_for_each(|&(start, end)| -> Result<(), ProcessingError> {
+                        process_chunk(
+                            &mmap[start_offset + start..start_offset + end],
+                            &process_line,
+                        )?;
+                        consumed.fetch_add((end - start) as u64, Ordering::Relaxed);
+                        report_progress();
+                        Ok(())
+                    })?;
+                checkpoint((start_offset + batch[batch.len() - 1].1) as u64)?;
+            }
+        }
+        DumpSource::Stream(mut reader) => {
+            if start_offset > 0 {
+                println!("Skipping the first {} decompressed bytes", start_offset);
+                let skipped = std::io::copy(
+                    &mut reader.by_ref().take(start_offset),
+                    &mut std::io::sink(),
+                )?;
+                if skipped < start_offset {
+                    return Err(std::io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "Resume offset lies beyond the end of the dump",
+                    )
+                    .into());
+                }
+            }
+            // The receiver is shared with the worker threads of each batch
+            let receiver = Mutex::new(spawn_chunk_reader(reader));
+            let mut offset = start_offset;
+            loop {
+                let processed = AtomicU64::new(0);
+                let chunks = AtomicU64::new(0);
+                std::iter::from_fn(|| receiver.lock().unwrap().next())
+                    .take(batch_size)
+                    .par_bridge()
+                    .try_for_each(|chunk| -> Result<(), ProcessingError> {
+                        let chunk = chunk?;
+                        process_chunk(&chunk, &process_line)?;
+                        processed.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                        chunks.fetch_add(1, Ordering::Relaxed);
+                        report_progress();
+                        Ok(())
+                    })?;
+                if chunks.into_inner() == 0 {
+                    break;
+                }
+                offset += processed.into_inner();
+                checkpoint(offset)?;
+            }
+        }
+    }
+
+    // Clear progress line
+    println!(
+        "\rProcessing: 100% | Completed in {:.0}s                 ",
+        start_time.elapsed().as_secs()
+    );
+    Ok(())
+}
@@ -0,0 +1,906 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::config::{Config, MonolingualFilter, RankFilter};
+use crate::coordinate::extract_coordinate;
+use crate::gender::Gender;
+use crate::grammar::decapitalize;
+use crate::label_cache::LabelCache;
+use crate::locale::Locale;
+use crate::properties::Properties;
+use crate::quantity::extract_quantity;
+use crate::template::qualifier_slots;
+use crate::utils::{
+    collapse_whitespace, commons_url, fnv1a, generate_snak_type_text, generate_text,
+    property_templates, vec_to_and_string, ResolvedValue, FNV_OFFSET_BASIS,
+};
+
+// Additional, manually added property keys
+const DESCRIPTIONS: &str = "descriptions";
+const PERSON_DESCRIPTIONS: &str = "person_descriptions";
+const ALIASES: &str = "aliases";
+const PERSON_ALIASES: &str = "person_aliases";
+
+// Snak types of unknown and absent values, also the keys of their generic templates
+const SOMEVALUE: &str = "somevalue";
+const NOVALUE: &str = "novalue";
+
+pub const ENTITY_URL_PREFIX: &str = "http://www.wikidata.org/entity/Q";
+
+#[derive(Deserialize, Debug)]
+pub struct Sitelink {
+    // title: String,               // The title of the page on the specific site
+    // badges: Option<Vec<String>>, // Optional badges associated with the link
+    // url: Option<String>,         // Optional URL of the page
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WikidataEntity {
+    pub id: String,
+    pub claims: Option<Map<String, Value>>,
+    pub labels: Option<Map<String, Value>>,
+    pub descriptions: Option<Map<String, Value>>,
+    pub aliases: Option<Map<String, Value>>,
+    // #[serde(default)]
+    pub sitelinks: Option<HashMap<String, Sitelink>>,
+}
+
+impl WikidataEntity {
+    /// Numeric part of a Q identifier, e.g. 42 for Q42, or `None` for other entities
+    pub fn numeric_id(&self) -> Option<u32> {
+        self.id.strip_prefix('Q')?.parse().ok()
+    }
+
+    /// Numeric part of a P identifier, e.g. 1630 for P1630, or `None` for other entities
+    pub fn property_id(&self) -> Option<u32> {
+        property_number(&self.id)
+    }
+
+    /// Formatter URL (P1630) of a property, where `$1` is replaced by the value of an external
+    /// identifier, e.g. `https://viaf.org/viaf/$1` for P214
+    pub fn formatter_url(&self) -> Option<&str> {
+        ranked_statements(self.claims.as_ref()?.get("P1630")?, RankFilter::Preferred).first()?
+            ["mainsnak"]["datavalue"]["value"]
+            .as_str()
+    }
+
+    /// Unit symbol (P5061) of the entity, preferably in the first of the given languages
+    pub fn unit_symbol(&self, languages: &[String]) -> Option<&str> {
+        self.monolingual_claim("P5061", languages)
+            .map(|(_, symbol)| symbol)
+            .or_else(|| {
+                ranked_statements(self.claims.as_ref()?.get("P5061")?, RankFilter::Normal)
+                    .first()?["mainsnak"]["datavalue"]["value"]["text"]
+                    .as_str()
+            })
+    }
+
+    /// Female form of the label (P2521), e.g. actress for actor, in the first of the given
+    /// languages that has one, with the index of that language
+    pub fn female_label(&self, languages: &[String]) -> Option<(usize, &str)> {
+        self.monolingual_claim("P2521", languages)
+    }
+
+    /// Male form of the label (P3321), in the first of the given languages that has one, with the
+    /// index of that language
+    pub fn male_label(&self, languages: &[String]) -> Option<(usize, &str)> {
+        self.monolingual_claim("P3321", languages)
+    }
+
+    /// Monolingual text value of a property in the first of the given languages that has one,
+    /// with the index of that language
+    fn monolingual_claim(&self, key: &str, languages: &[String]) -> Option<(usize, &str)> {
+        let values: Vec<&Value> =
+            ranked_statements(self.claims.as_ref()?.get(key)?, RankFilter::Normal)
+                .into_iter()
+                .map(|statement| &statement["mainsnak"]["datavalue"]["value"])
+                .collect();
+        languages.iter().enumerate().find_map(|(index, lang)| {
+            values
+                .iter()
+                .find(|value| value["language"].as_str() == Some(lang))
+                .and_then(|value| value["text"].as_str())
+                .map(|text| (index, text))
+        })
+    }
+
+    /// Wikimedia language code (P424) of a language, e.g. `ja` for Japanese. Only entities with an
+    /// ISO 639 code are considered languages, so e.g. a Wikipedia edition is skipped.
+    pub fn language_code(&self) -> Option<&str> {
+        let claims = self.claims.as_ref()?;
+        if !["P218", "P219", "P220"]
+            .iter()
+            .any(|key| claims.contains_key(*key))
+        {
+            return None;
+        }
+        ranked_statements(claims.get("P424")?, RankFilter::Preferred).first()?["mainsnak"]
+            ["datavalue"]["value"]
+            .as_str()
+    }
+
+    /// Classes the entity is a subclass of (P279), e.g. 486972 (human settlement) for Q515 (city)
+    pub fn superclasses(&self) -> Vec<u32> {
+        self.claims
+            .as_ref()
+            .and_then(|claims| claims.get("P279"))
+            .map_or(Vec::new(), |statements| {
+                ranked_statements(statements, RankFilter::Normal)
+                    .into_iter()
+                    .filter_map(|statement| {
+                        statement["mainsnak"]["datavalue"]["value"]["numeric-id"].as_u64()
+                    })
+                    .map(|class| class as u32)
+                    .collect()
+            })
+    }
+
+    /// Label of the entity in the first of the given languages that has one, with the index of
+    /// that language
+    pub fn label(&self, languages: &[String]) -> Option<(usize, &str)> {
+        let (index, label) = first_in_languages(self.labels.as_ref()?, languages)?;
+        Some((index, label.get("value")?.as_str()?))
+    }
+}
+
+/// Numeric part of a property key, e.g. 1630 for P1630
+fn property_number(key: &str) -> Option<u32> {
+    key.strip_prefix('P')?.parse().ok()
+}
+
+/// Value of the first of the given languages that is present in a language map of an entity,
+/// e.g. its labels, descriptions or aliases, with the index of that language
+fn first_in_languages<'a>(
+    map: &'a Map<String, Value>,
+    languages: &[String],
+) -> Option<(usize, &'a Value)> {
+    languages
+        .iter()
+        .enumerate()
+        .find_map(|(index, lang)| map.get(lang).map(|value| (index, value)))
+}
+
+/// Value of a claim, where references to other entities are not resolved yet
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ClaimValue {
+    /// Literal text, e.g. a formatted date, coordinate or external identifier
+    Text(String),
+    /// Reference to another entity, e.g. 55 for Q55
+    Entity(u32),
+    /// Formatted amount with a unit entity, e.g. metre, which is shown as its symbol if any
+    Quantity { amount: String, unit: u32 },
+    /// Text in another language than the rendered ones, with its language code, e.g. `ja`
+    Monolingual { text: String, language: String },
+    /// Formatted coordinates on another globe than Earth, e.g. the Moon
+    Coordinate { text: String, globe: u32 },
+    /// External identifier of the given property, shown as a URL when the property has a
+    /// formatter URL
+    ExternalId { property: u32, id: String },
+    /// Reference to a property, e.g. 1630 for P1630
+    Property(u32),
+    /// Some value that is not known, e.g. an unknown father
+    SomeValue,
+    /// Explicitly no value, e.g. no children
+    NoValue,
+}
+
+impl ClaimValue {
+    /// Snak type of a value without an actual value, i.e. `somevalue` or `novalue`
+    fn snak_type(&self) -> Option<&'static str> {
+        match self {
+            ClaimValue::SomeValue => Some(SOMEVALUE),
+            ClaimValue::NoValue => Some(NOVALUE),
+            _ => None,
+        }
+    }
+
+    /// Resolve the value to text, returning `None` for unknown entities. Entities use the form of
+    /// their label that agrees with the gender of the subject, if any, e.g. actress instead of
+    /// actor. The languages of the resolved labels are added to `used_languages`.
+    fn resolve(
+        &self,
+        locale: &Locale,
+        resolver: &LabelCache,
+        gender: Option<Gender>,
+        used_languages: &mut BTreeSet<usize>,
+    ) -> Option<String> {
+        let mut resolve_label = |id: u32| {
+            resolver.get_with_language(id).map(|(label, language)| {
+                used_languages.insert(language);
+                label.to_string()
+            })
+        };
+        match self {
+            ClaimValue::Text(text) => Some(text.clone()),
+            ClaimValue::Entity(id) => {
+                match gender.and_then(|gender| resolver.gendered_label(*id, gender)) {
+                    Some((label, language)) => {
+                        used_languages.insert(language);
+                        Some(label.to_string())
+                    }
+                    None => resolve_label(*id),
+                }
+            }
+            ClaimValue::Quantity { amount, unit } => Some(format!(
+                "{} {}",
+                amount,
+                resolver
+                    .unit_symbol(*unit)
+                    .map(str::to_string)
+                    .or_else(|| resolve_label(*unit))
+                    .unwrap_or_else(|| format!("{}{}", ENTITY_URL_PREFIX, unit))
+            )),
+            ClaimValue::Monolingual { text, language } => Some(
+                locale
+                    .monolingual_format
+                    .replacen(
+                        "{}",
+                        &resolver
+                            .language_item(language)
+                            .and_then(&mut resolve_label)
+                            .unwrap_or_else(|| language.clone()),
+                        1,
+                    )
+                    .replacen("{}", text, 1),
+            ),
+            ClaimValue::Coordinate { text, globe } => Some(
+                locale.globe_format.replacen("{}", text, 1).replacen(
+                    "{}",
+                    &resolve_label(*globe)
+                        .unwrap_or_else(|| format!("{}{}", ENTITY_URL_PREFIX, globe)),
+                    1,
+                ),
+            ),
+            ClaimValue::ExternalId { property, id } => Some(
+                resolver
+                    .formatter_url(*property)
+                    .map_or_else(|| id.clone(), |url| url.replace("$1", id)),
+            ),
+            ClaimValue::Property(id) => resolver.property_label(*id).map(|(label, language)| {
+                used_languages.insert(language);
+                label.to_string()
+            }),
+            ClaimValue::SomeValue | ClaimValue::NoValue => None,
+        }
+    }
+}
+
+/// Value of a statement, with the qualifiers that further describe it, e.g. its start time
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatementValue {
+    pub value: ClaimValue,
+    /// Qualifier property keys with their values, in the order of the statement
+    pub qualifiers: Vec<(String, ClaimValue)>,
+}
+
+impl StatementValue {
+    /// Value without qualifiers, e.g. for descriptions and aliases
+    fn plain(value: ClaimValue) -> Self {
+        Self {
+            value,
+            qualifiers: Vec::new(),
+        }
+    }
+
+    /// Resolve the value to text, followed by its qualifiers rendered using their templates,
+    /// e.g. "president of the United States from 1789 until 1797". Qualifiers referenced by the
+    /// template of the property, in `slotted`, are resolved separately instead.
+    fn resolve(
+        &self,
+        properties: &Properties,
+        resolver: &LabelCache,
+        gender: Option<Gender>,
+        slotted: &[String],
+        used_languages: &mut BTreeSet<usize>,
+    ) -> Option<ResolvedValue> {
+        let mut text = self
+            .value
+            .resolve(&properties.locale, resolver, gender, used_languages)?;
+        let mut slots: HashMap<String, Vec<String>> = HashMap::new();
+        for (key, qualifier) in &self.qualifiers {
+            let Some(value) =
+                qualifier.resolve(&properties.locale, resolver, gender, used_languages)
+            else {
+                continue;
+            };
+            if slotted.contains(key) {
+                slots.entry(key.clone()).or_default().push(value);
+            } else if let Some(template) = properties.qualifier_template(key) {
+                text.push(' ');
+                text.push_str(&template.replacen("{}", &value, 1));
+            }
+        }
+        Some(ResolvedValue {
+            text,
+            qualifiers: slots
+                .into_iter()
+                .map(|(key, values)| (key, vec_to_and_string(&values, &properties.locale)))
+                .collect(),
+        })
+    }
+}
+
+/// All values of a single property key
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claim {
+    pub key: String,
+    pub values: Vec<StatementValue>,
+}
+
+/// Entity with its description, aliases and claims, ready to be rendered as text
+/// once the referenced entities can be resolved to their labels.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExtractedEntity {
+    /// Entity id, e.g. Q42, which picks the variants of the templates
+    pub id: String,
+    pub label: String,
+    /// Language of the label
+    pub label_language: String,
+    /// First P31 (instance of) class, used to categorize the output
+    pub category: Option<u32>,
+    /// All P31 (instance of) classes, used to pick class specific templates
+    pub classes: Vec<u32>,
+    /// Gender of a human, `None` for other entities
+    pub gender: Option<Gender>,
+    pub claims: Vec<Claim>,
+}
+
+/// Convert the JSON of a single snak, i.e. the main value or a qualifier of a statement, to a
+/// claim value. The datatype of the property in the properties file takes precedence over the
+/// datatype of the snak. Monolingual texts in other than the given languages are dropped or
+/// tagged with their language, depending on the configuration.
+fn extract_value(
+    snak: &Value,
+    languages: &[String],
+    properties: &Properties,
+    config: &Config,
+) -> Option<ClaimValue> {
+    match snak["snaktype"].as_str() {
+        Some(SOMEVALUE) => return Some(ClaimValue::SomeValue),
+        Some(NOVALUE) => return Some(ClaimValue::NoValue),
+        _ => {}
+    }
+    let datavalue = &snak["datavalue"];
+    let property = snak["property"].as_str();
+    // The datatype in the dump is authoritative, the properties file only fills in when it lacks
+    let datatype = snak["datatype"]
+        .as_str()
+        .or_else(|| property.and_then(|key| properties.datatype(key)));
+
+    match datatype {
+        Some("external-id") => {
+            let id = datavalue["value"].as_str()?.to_string();
+            return Some(match property.and_then(property_number) {
+                Some(property) => ClaimValue::ExternalId { property, id },
+                None => ClaimValue::Text(id),
+            });
+        }
+        Some("wikibase-property") => {
+            return datavalue["value"]["numeric-id"]
+                .as_u64()
+                .map(|id| ClaimValue::Property(id as u32));
+        }
+        Some("commonsMedia") => {
+            return datavalue["value"]
+                .as_str()
+                .map(|file| ClaimValue::Text(commons_url(&format!("File:{}", file))));
+        }
+        // Both refer to a page in the Data namespace of Commons, e.g. Data:Berlin.map
+        Some("geo-shape" | "tabular-data") => {
+            return datavalue["value"]
+                .as_str()
+                .map(|page| ClaimValue::Text(commons_url(page)));
+        }
+        // TeX formulas and LilyPond notation may span multiple lines
+        Some("math" | "musical-notation") => {
+            return datavalue["value"]
+                .as_str()
+                .map(|text| ClaimValue::Text(collapse_whitespace(text)));
+        }
+        _ => {}
+    }
+
+    match datavalue["type"].as_str() {
+        Some("wikibase-entityid") => datavalue["value"]["numeric-id"]
+            .as_number()
+            .map(|instance| ClaimValue::Entity(instance.as_u64().unwrap_or(0) as u32)),
+        Some("globecoordinate") => extract_coordinate(
+            &datavalue["value"],
+            &properties.locale,
+            config.coordinate_format,
+        ),
+        Some("quantity") => {
+            extract_quantity(&datavalue["value"], &properties.locale, config.unit_system)
+        }
+        Some("monolingualtext") => {
+            let text = datavalue["value"]["text"].as_str()?.to_string();
+            let language = datavalue["value"]["language"].as_str().unwrap_or_default();
+            if in_languages(language, languages) {
+                Some(ClaimValue::Text(text))
+            } else {
+                match config.monolingual {
+                    MonolingualFilter::Drop => None,
+                    MonolingualFilter::Tag => Some(ClaimValue::Monolingual {
+                        text,
+                        language: language.to_string(),
+                    }),
+                }
+            }
+        }
+        // None => Some(missing_date.clone())
+        Some("time") => properties
+            .locale
+            .format_time(&datavalue["value"])
+            .map(ClaimValue::Text),
+        Some("string") => datavalue["value"]
+            .as_str()
+            .map(|instance| ClaimValue::Text(instance.to_string())),
+        None => None,
+        _ => datavalue["value"]
+            .as_str()
+            .map(|instance| ClaimValue::Text(instance.to_string())),
+    }
+}
+
+/// Sentence and question generated for a single claim
+pub struct RenderedClaim<'a> {
+    pub key: &'a str,
+    pub sentence: Option<String>,
+    pub question: Option<String>,
+}
+
+/// Entity rendered to text, ready to be written to the output
+pub struct RenderedEntity<'a> {
+    pub category: String,
+    pub claims: Vec<RenderedClaim<'a>>,
+    /// Comma separated languages of the labels used, in the order of the fallback chain
+    pub label_languages: String,
+}
+
+impl RenderedEntity<'_> {
+    /// All sentences, one per line
+    pub fn sentences(&self) -> String {
+        self.claims
+            .iter()
+            .filter_map(|claim| claim.sentence.as_deref())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    /// All questions, one per line
+    pub fn questions(&self) -> String {
+        self.claims
+            .iter()
+            .filter_map(|claim| claim.question.as_deref())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+}
+
+/// Statements of a property that should be rendered, based on their rank
+fn ranked_statements(statements: &Value, rank: RankFilter) -> Vec<&Value> {
+    let statements = statements.as_array().map_or(&[][..], |s| s.as_slice());
+    let has_rank = |statement: &Value, rank: &str| statement["rank"].as_str() == Some(rank);
+    match rank {
+        RankFilter::All => statements.iter().collect(),
+        RankFilter::Preferred if statements.iter().any(|s| has_rank(s, "preferred")) => statements
+            .iter()
+            .filter(|s| has_rank(s, "preferred"))
+            .collect(),
+        RankFilter::Normal | RankFilter::Preferred => statements
+            .iter()
+            .filter(|s| !has_rank(s, "deprecated"))
+            .collect(),
+    }
+}
+
+/// Whether a language code, e.g. `en-gb`, is one of the given languages or a variant of one
+fn in_languages(language: &str, languages: &[String]) -> bool {
+    languages.iter().any(|lang| {
+        language == lang
+            || language
+                .strip_prefix(lang.as_str())
+                .is_some_and(|variant| variant.starts_with('-'))
+    })
+}
+
+/// Convert the JSON of a single statement to its value, with the qualifiers that have a template
+fn extract_statement(
+    statement: &Value,
+    languages: &[String],
+    properties: &Properties,
+    config: &Config,
+) -> Option<StatementValue> {
+    let value = extract_value(&statement["mainsnak"], languages, properties, config)?;
+
+    // Qualifiers with a template of their own, or referenced by the template of the property
+    let slotted = statement["mainsnak"]["property"]
+        .as_str()
+        .map(|key| properties.template_qualifiers(key))
+        .unwrap_or_default();
+    let qualifiers = &statement["qualifiers"];
+    let order: Vec<&str> = match statement["qualifiers-order"].as_array() {
+        Some(order) => order.iter().filter_map(|key| key.as_str()).collect(),
+        None => qualifiers
+            .as_object()
+            .map_or(Vec::new(), |q| q.keys().map(String::as_str).collect()),
+    };
+    let qualifiers = order
+        .into_iter()
+        .filter(|key| {
+            properties.qualifier_template(key).is_some() || slotted.iter().any(|slot| slot == key)
+        })
+        .flat_map(|key| {
+            qualifiers[key]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|snak| extract_value(snak, languages, properties, config))
+                // Unknown or absent qualifier values are left out
+                .filter(|value| value.snak_type().is_none())
+                .map(move |value| (key.to_string(), value))
+        })
+        .collect();
+
+    Some(StatementValue { value, qualifiers })
+}
+
+impl ExtractedEntity {
+    /// Extract the label, description, aliases and claims of an entity in the first of the given
+    /// languages that has them, keeping the statements allowed by the configured rank. Returns
+    /// `None` when the entity is incomplete, lacks a label in all languages, or is a non-famous
+    /// human.
+    pub fn from_entity(
+        entity: &WikidataEntity,
+        languages: &[String],
+        properties: &Properties,
+        config: &Config,
+    ) -> Option<Self> {
+        let rank = config.rank;
+        let (Some(claims), Some(labels), Some(descriptions), Some(aliases)) = (
+            &entity.claims,
+            &entity.labels,
+            &entity.descriptions,
+            &entity.aliases,
+        ) else {
+            return None;
+        };
+        let (label_index, label) = first_in_languages(labels, languages)?;
+        let label = label.get("value")?.as_str()?;
+
+        let instance_of: Vec<u64> = claims.get("P31").map_or(Vec::new(), |instances| {
+            ranked_statements(instances, rank)
+                .into_iter()
+                .filter_map(|i| {
+                    i["mainsnak"]["datavalue"]["value"]["numeric-id"]
+                        .as_number()
+                        .map(|instance| instance.as_u64().unwrap_or(0))
+                })
+                .collect()
+        });
+
+        // Q5 Human, Q15632617 Fictional Human
+        let gender = if instance_of.contains(&5) || instance_of.contains(&15632617) {
+            let is_famous = entity
+                .sitelinks
+                .as_ref()
+                .is_some_and(|sitelinks| !sitelinks.is_empty());
+            if !is_famous {
+                return None; // Skip non-famous humans.
+            }
+            // Famous human, having at least one wikipage in his/her name.
+            let genders: Vec<u64> = claims.get("P21").map_or(Vec::new(), |genders| {
+                ranked_statements(genders, rank)
+                    .into_iter()
+                    .filter_map(|i| i["mainsnak"]["datavalue"]["value"]["numeric-id"].as_u64())
+                    .collect()
+            });
+            Some(Gender::from_values(&genders))
+        } else {
+            None
+        };
+        let is_human = gender.is_some();
+
+        let mut extracted_claims = Vec::new();
+
+        let description = decapitalize(
+            first_in_languages(descriptions, languages)
+                .and_then(|(_, obj)| obj.get("value"))
+                .and_then(|v| v.as_str())
+                .unwrap_or(""),
+        );
+        if !description.is_empty() {
+            let key = if is_human {
+                PERSON_DESCRIPTIONS
+            } else {
+                DESCRIPTIONS
+            };
+            extracted_claims.push(Claim {
+                key: key.to_string(),
+                values: vec![StatementValue::plain(ClaimValue::Text(description))],
+            });
+        }
+
+        let aliases = first_in_languages(aliases, languages)
+            .and_then(|(_, value)| value.as_array())
+            .map(|values| {
+                values
+                    .iter()
+                    .map(|v| v.get("value").and_then(|v| v.as_str()).unwrap_or(""))
+                    .filter(|alias| *alias != label)
+                    .map(|alias| StatementValue::plain(ClaimValue::Text(alias.to_string())))
+                    .collect::<Vec<StatementValue>>()
+            })
+            .unwrap_or_default();
+        if !aliases.is_empty() {
+            let key = if is_human { PERSON_ALIASES } else { ALIASES };
+            extracted_claims.push(Claim {
+                key: key.to_string(),
+                values: aliases,
+            });
+        }
+
+        // Process all claims
+        for (key, value) in claims {
+            let values: Vec<StatementValue> = ranked_statements(value, rank)
+                .into_iter()
+                .filter_map(|i| extract_statement(i, languages, properties, config))
+                .collect();
+            if !values.is_empty() {
+                extracted_claims.push(Claim {
+                    key: key.to_string(),
+                    values,
+                });
+            }
+        }
+
+        Some(Self {
+            id: entity.id.clone(),
+            label: label.to_string(),
+            label_language: languages[label_index].clone(),
+            category: instance_of.first().map(|key| *key as u32),
+            classes: instance_of.iter().map(|key| *key as u32).collect(),
+            gender,
+            claims: extracted_claims,
+        })
+    }
+
+    /// Keys of the class specific templates of a property that apply to the entity, most specific
+    /// first: templates of its own classes, followed by those of the nearest superclasses
+    fn class_scopes<'a>(
+        &self,
+        properties: &'a Properties,
+        resolver: &LabelCache,
+        key: &str,
+        class_distances: &mut Option<HashMap<u32, usize>>,
+    ) -> Vec<&'a str> {
+        let mut scopes: Vec<(usize, &str)> = properties
+            .class_scopes(key)
+            .iter()
+            .filter_map(|scope| {
+                let distance = if self.classes.contains(&scope.class) {
+                    0
+                } else if scope.subclasses {
+                    *class_distances
+                        .get_or_insert_with(|| resolver.class_distances(&self.classes))
+                        .get(&scope.class)?
+                } else {
+                    return None;
+                };
+                Some((distance, scope.key.as_str()))
+            })
+            .collect();
+        // Stable, so templates of equally specific classes keep the order of the properties file
+        scopes.sort_by_key(|(distance, _)| *distance);
+        scopes.into_iter().map(|(_, key)| key).collect()
+    }
+
+    /// Render the entity to its category, sentences and questions, resolving entity references
+    /// to their labels. Returns `None` when no sentences or questions could be generated.
+    pub fn render(
+        &self,
+        properties: &Properties,
+        resolver: &LabelCache,
+        config: &Config,
+    ) -> Option<RenderedEntity<'_>> {
+        let mut rendered_claims = Vec::new();
+        let mut used_languages = BTreeSet::new();
+        // The same seed and entity always render the same variants
+        let seed = fnv1a(
+            fnv1a(FNV_OFFSET_BASIS, &config.seed.to_le_bytes()),
+            self.id.as_bytes(),
+        );
+
+        // Superclasses of the classes of the entity, only looked up when needed
+        let mut class_distances = None;
+
+        for claim in &self.claims {
+            let scopes = self.class_scopes(properties, resolver, &claim.key, &mut class_distances);
+            let templates_for = |count: usize| {
+                property_templates(properties, &claim.key, &scopes, self.gender, count, seed)
+            };
+            let mut resolve = |templates: &Option<(String, String)>| -> Vec<ResolvedValue> {
+                let slotted: Vec<String> = templates
+                    .iter()
+                    .flat_map(|(sentence, question)| {
+                        qualifier_slots(sentence).chain(qualifier_slots(question))
+                    })
+                    .map(str::to_string)
+                    .collect();
+                claim
+                    .values
+                    .iter()
+                    .filter_map(|value| {
+                        value.resolve(
+                            properties,
+                            resolver,
+                            self.gender,
+                            &slotted,
+                            &mut used_languages,
+                        )
+                    })
+                    .collect()
+            };
+            let mut templates = templates_for(claim.values.len());
+            let mut values = resolve(&templates);
+            // Values that could not be resolved may leave a single value for a plural template
+            if (values.len() > 1) != (claim.values.len() > 1) {
+                templates = templates_for(values.len());
+                values = resolve(&templates);
+            }
+            let (sentence, question) = if !values.is_empty() {
+                let Some(templates) = &templates else {
+                    continue;
+                };
+                generate_text(
+                    templates,
+                    &properties.locale,
+                    &self.label,
+                    &values,
+                    self.gender,
+                    // Refer to the subject by its pronoun once it has been introduced
+                    config.pronouns
+                        && rendered_claims
+                            .iter()
+                            .any(|claim: &RenderedClaim| claim.sentence.is_some()),
+                )
+            } else if let Some(snak_type) = claim
+                .values
+                .iter()
+                .find_map(|value| value.value.snak_type())
+            {
+                // Only rendered when the property has no known values at all
+                generate_snak_type_text(properties, snak_type, &claim.key, &self.label, seed)
+            } else {
+                continue;
+            };
+            if sentence.is_some() || question.is_some() {
+                rendered_claims.push(RenderedClaim {
+                    key: &claim.key,
+                    sentence,
+                    question,
+                });
+            }
+        }
+
+        if rendered_claims.is_empty() {
+            return None;
+        }
+        let category = self
+            .category
+            .and_then(|key| resolver.get(key).map(str::to_string)) // Ensure ownership
+            .unwrap_or_else(|| "misc".to_string()); // Ensure the fallback is owned
+        let languages = &resolver.source.languages;
+        let label_languages = languages
+            .iter()
+            .enumerate()
+            .filter(|(index, lang)| used_languages.contains(index) || **lang == self.label_language)
+            .map(|(_, lang)| lang.as_str())
+            .collect::<Vec<&str>>()
+            .join(",");
+        Some(RenderedEntity {
+            category,
+            claims: rendered_claims,
+            label_languages,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CoordinateFormat, UnitSystem};
+    use crate::label_cache::{CacheSource, ClassCache, CollectedLabels};
+    use dashmap::DashMap;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn config() -> Config {
+        Config {
+            langs: vec!["en".to_string()],
+            fallback: Vec::new(),
+            output_dir: String::new(),
+            recreate_cache: false,
+            single_pass: false,
+            rank: RankFilter::Normal,
+            unit_system: UnitSystem::Original,
+            coordinate_format: CoordinateFormat::Dms,
+            monolingual: MonolingualFilter::Drop,
+            pronouns: false,
+            seed: 0,
+            aligned: false,
+            resume: false,
+            checkpoint_interval: 300,
+        }
+    }
+
+    /// Empty label cache, saved to and opened from the temporary directory
+    fn resolver() -> LabelCache {
+        let path = |name: &str| {
+            std::env::temp_dir().join(format!("wdsdg-{}-entity-{}.bin", std::process::id(), name))
+        };
+        let source = CacheSource {
+            dump_path: String::new(),
+            dump_size: 0,
+            dump_modified: 0,
+            content_hash: 0,
+            languages: vec!["en".to_string()],
+        };
+        ClassCache::save(&path("classes"), &source, DashMap::new()).unwrap();
+        let classes = Arc::new(ClassCache::open(&path("classes")).unwrap());
+        LabelCache::save(&path("labels"), &source, CollectedLabels::default()).unwrap();
+        let resolver = LabelCache::open(&path("labels"), classes).unwrap();
+        std::fs::remove_file(path("classes")).unwrap();
+        std::fs::remove_file(path("labels")).unwrap();
+        resolver
+    }
+
+    /// Sentences and questions of a human with the given English description
+    fn render_description(description: &str) -> (String, String) {
+        let entity: WikidataEntity = serde_json::from_value(json!({
+            "id": "Q1",
+            "claims": {"P31": [{
+                "mainsnak": {
+                    "snaktype": "value",
+                    "property": "P31",
+                    "datatype": "wikibase-item",
+                    "datavalue": {"value": {"numeric-id": 5, "id": "Q5"}}
+                },
+                "rank": "normal"
+            }]},
+            "labels": {"en": {"language": "en", "value": "Pat"}},
+            "descriptions": {"en": {"language": "en", "value": description}},
+            "aliases": {},
+            "sitelinks": {"enwiki": {"site": "enwiki", "title": "Pat"}}
+        }))
+        .unwrap();
+        let languages = ["en".to_string()];
+        let properties = Properties::load("en").unwrap();
+        let config = config();
+        let extracted =
+            ExtractedEntity::from_entity(&entity, &languages, &properties, &config).unwrap();
+        let rendered = extracted.render(&properties, &resolver(), &config).unwrap();
+        (rendered.sentences(), rendered.questions())
+    }
+
+    #[test]
+    fn keeps_initialisms_of_descriptions() {
+        let (sentences, questions) = render_description("NASA astronaut");
+        assert!(
+            sentences.contains("Pat is a NASA astronaut."),
+            "{}",
+            sentences
+        );
+        assert!(questions.contains("[NASA astronaut]"), "{}", questions);
+        let (sentences, _) = render_description("FBI agent");
+        assert!(sentences.contains("Pat is an FBI agent."), "{}", sentences);
+        let (sentences, questions) = render_description("American astronaut");
+        assert!(
+            sentences.contains("Pat is an american astronaut."),
+            "{}",
+            sentences
+        );
+        assert!(questions.contains("[american astronaut]"), "{}", questions);
+    }
+}
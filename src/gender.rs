@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+// Values of sex or gender (P21) that are treated as male or female
+const MALE: [u64; 3] = [
+    6581097, // male
+    2449503, // trans man
+    44148,   // male organism
+];
+const FEMALE: [u64; 3] = [
+    6581072, // female
+    1052281, // trans woman
+    43445,   // female organism
+];
+
+/// Gender of a human, based on its sex or gender (P21) statements
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Gender {
+    Male,
+    Female,
+    /// Any other gender, e.g. non-binary, intersex or genderfluid, or conflicting statements
+    NonBinary,
+    /// No statements, or only unknown values
+    Unknown,
+}
+
+impl Gender {
+    /// Gender of the given P21 values, e.g. 6581072 for Q6581072 (female)
+    pub fn from_values(values: &[u64]) -> Self {
+        let gender_of = |value: &u64| {
+            if MALE.contains(value) {
+                Gender::Male
+            } else if FEMALE.contains(value) {
+                Gender::Female
+            } else {
+                Gender::NonBinary
+            }
+        };
+        let mut genders = values.iter().map(gender_of);
+        match genders.next() {
+            None => Gender::Unknown,
+            Some(first) if genders.all(|gender| gender == first) => first,
+            Some(_) => Gender::NonBinary,
+        }
+    }
+
+    /// Suffix of the key of a gender specific template, e.g. `P26#female`
+    pub fn variant(&self) -> Option<&'static str> {
+        match self {
+            Gender::Male => Some("male"),
+            Gender::Female => Some("female"),
+            Gender::NonBinary => Some("nonbinary"),
+            Gender::Unknown => None,
+        }
+    }
+}
@@ -0,0 +1,435 @@
+use crate::template;
+use crate::utils::{lowercase_first, uppercase_first};
+
+/// Beginnings of English words that start with a vowel letter but a consonant sound, e.g. a
+/// university, a UniProt entry or a European
+const CONSONANT_SOUND_PREFIXES: [&str; 28] = [
+    "eu", "ewe", "once", "u.", "ubiq", "uef", "uga", "ukr", "uku", "unanim", "unes", "unic",
+    "unif", "union", "unip", "uniq", "unis", "unit", "univ", "uran", "urin", "uro", "usa", "use",
+    "usu", "ute", "uti", "uto",
+];
+/// Acronyms that are pronounced as a word rather than letter by letter, e.g. a NATO summit or an
+/// OPEC member
+const WORD_ACRONYMS: [&str; 15] = [
+    "AIDS", "ASEAN", "CERN", "FIFA", "LEGO", "NASA", "NASCAR", "NATO", "OPEC", "SARS", "UEFA",
+    "UNESCO", "UNICEF", "UNIX", "WHO",
+];
+/// Beginnings of English words that start with a silent h, e.g. an hour or an honorary doctorate
+const SILENT_H_PREFIXES: [&str; 5] = ["heir", "honest", "honor", "honour", "hour"];
+/// Letters whose English name starts with a vowel sound, e.g. an FBI agent or an MP
+const VOWEL_SOUND_LETTERS: &str = "AEFHILMNORSX";
+
+/// Dutch nouns with the article het, which compounds ending in them share, e.g. het eiland
+const DUTCH_HET_NOUNS: [&str; 48] = [
+    "album",
+    "bedrijf",
+    "beroep",
+    "bisdom",
+    "boek",
+    "bureau",
+    "centrum",
+    "college",
+    "concert",
+    "deel",
+    "dier",
+    "district",
+    "dorp",
+    "festival",
+    "gebergte",
+    "gebied",
+    "gebouw",
+    "geslacht",
+    "hoofd",
+    "huis",
+    "instituut",
+    "jaar",
+    "kanaal",
+    "kasteel",
+    "kind",
+    "klooster",
+    "land",
+    "lid",
+    "lied",
+    "meer",
+    "merk",
+    "ministerie",
+    "monument",
+    "museum",
+    "nummer",
+    "orgaan",
+    "park",
+    "plein",
+    "programma",
+    "rijk",
+    "schip",
+    "spel",
+    "stadion",
+    "station",
+    "stuk",
+    "team",
+    "water",
+    "werk",
+];
+/// Suffixes of Dutch nouns with the article het: diminutives and isms, e.g. het realisme
+const DUTCH_HET_SUFFIXES: [&str; 2] = ["je", "isme"];
+/// Dutch nouns with the article het that end in a suffix of nouns with the article de, e.g. het
+/// schilderij. Compounds do not share their article, e.g. de republiek.
+const DUTCH_HET_WORDS: [&str; 4] = ["ding", "mozaïek", "publiek", "schilderij"];
+/// Suffixes of Dutch nouns with the article de, e.g. de vereniging or de universiteit
+const DUTCH_DE_SUFFIXES: [&str; 9] = [
+    "erij", "heid", "iek", "ing", "ist", "sie", "teit", "theek", "tie",
+];
+
+/// Grammar rules of the language of the sentences and questions, applied while their slots are
+/// filled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grammar {
+    /// a or an, depending on the sound of the next word
+    English,
+    /// de or het, depending on the next noun
+    Dutch,
+    /// Only capitalization
+    Other,
+}
+
+impl Grammar {
+    /// Grammar of a language code, e.g. English for `en` or `en-gb`
+    pub fn from_language(lang: &str) -> Self {
+        match lang.split('-').next().unwrap_or(lang) {
+            "en" => Grammar::English,
+            "nl" => Grammar::Dutch,
+            _ => Grammar::Other,
+        }
+    }
+
+    /// Fill the slots of a template, see `template::render`, with the articles of the template
+    /// that directly precede a slot agreeing with its value, e.g. `{subject} is an {object}.`
+    /// becomes "Leiden University is a university.", and capitalize the first word. Labels and
+    /// other values are never changed.
+    pub fn render(&self, template: &str, slots: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+        template::render(&self.adjust_articles(template, slots), slots)
+            .map(|text| capitalize(&text))
+    }
+
+    /// Correct the articles that directly precede a slot of the template
+    fn adjust_articles(&self, template: &str, slots: &dyn Fn(&str) -> Option<String>) -> String {
+        if *self == Grammar::Other {
+            return template.to_string();
+        }
+        let mut adjusted = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some((text, slot)) = rest.split_once('{') {
+            let Some((name, after)) = slot.split_once('}') else {
+                break;
+            };
+            let corrected = text.strip_suffix(' ').and_then(|before| {
+                let start = before.trim_end_matches(char::is_alphabetic);
+                let value = slots(name)?;
+                let article = self.article(&before[start.len()..], &value)?;
+                Some(format!("{}{} ", start, article))
+            });
+            adjusted.push_str(corrected.as_deref().unwrap_or(text));
+            adjusted.push('{');
+            adjusted.push_str(name);
+            adjusted.push('}');
+            rest = after;
+        }
+        adjusted.push_str(rest);
+        adjusted
+    }
+
+    /// Article that agrees with the first word of `next`, when `word` is an article of the
+    /// language
+    fn article(&self, word: &str, next: &str) -> Option<String> {
+        let next = next.split(' ').next().unwrap_or_default();
+        let correct = match (self, word.to_lowercase().as_str()) {
+            (Grammar::English, "a" | "an") => english_article(next)?,
+            (Grammar::Dutch, "de" | "het") => dutch_article(next)?,
+            _ => return None,
+        };
+        Some(if word.starts_with(char::is_uppercase) {
+            uppercase_first(correct)
+        } else {
+            correct.to_string()
+        })
+    }
+}
+
+/// English article of a word, based on the sound it starts with: a for a consonant sound, e.g.
+/// a university, and an for a vowel sound, e.g. an hour, an FBI agent or an 18th century house
+fn english_article(word: &str) -> Option<&'static str> {
+    let word = word.trim_start_matches(|c: char| !c.is_alphanumeric());
+    let first = word.chars().next()?;
+    let vowel_sound = if first.is_ascii_digit() {
+        // Eight, eleven and eighteen, also in e.g. 11,000 and 18 million
+        let digits: String = word
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == ',')
+            .filter(char::is_ascii_digit)
+            .collect();
+        digits.starts_with('8')
+            || (["11", "18"].iter().any(|start| digits.starts_with(start)) && digits.len() % 3 == 2)
+    } else if is_initialism(word) && !is_word_acronym(word) {
+        VOWEL_SOUND_LETTERS.contains(first.to_ascii_uppercase())
+    } else {
+        let lower = word.to_lowercase();
+        if SILENT_H_PREFIXES
+            .iter()
+            .any(|prefix| lower.starts_with(prefix))
+        {
+            true
+        } else if lower == "one"
+            || lower.starts_with("one-")
+            || CONSONANT_SOUND_PREFIXES
+                .iter()
+                .any(|prefix| lower.starts_with(prefix))
+        {
+            false
+        } else {
+            "aeiou".contains(first.to_ascii_lowercase())
+        }
+    };
+    Some(if vowel_sound { "an" } else { "a" })
+}
+
+/// Whether an English word is pronounced letter by letter: written in capitals, e.g. NCAA,
+/// NFL.com or the X of X-ray, or a dotted letter, e.g. U.S.
+fn is_initialism(word: &str) -> bool {
+    let head = word.split(['-', '.']).next().unwrap_or(word);
+    let dotted = word[head.len()..].starts_with('.');
+    head.chars()
+        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        && head.starts_with(|c: char| c.is_ascii_uppercase())
+        || dotted && head.chars().count() == 1
+}
+
+/// Whether an English word is an acronym that is pronounced as a word, e.g. NATO or NATO-led
+fn is_word_acronym(word: &str) -> bool {
+    let head = word.split(['-', '.']).next().unwrap_or(word);
+    WORD_ACRONYMS.contains(&head.trim_end_matches(|c: char| !c.is_alphanumeric()))
+}
+
+/// Dutch article of a noun, or `None` when it is unknown. Names keep their article, e.g. de
+/// Telegraaf, and so do adjectives, e.g. de grootste stad.
+fn dutch_article(word: &str) -> Option<&'static str> {
+    let word = word.trim_end_matches(|c: char| !c.is_alphanumeric());
+    if !word.starts_with(char::is_lowercase) {
+        return None;
+    }
+    if DUTCH_HET_WORDS.contains(&word)
+        || DUTCH_HET_NOUNS
+            .iter()
+            .chain(DUTCH_HET_SUFFIXES.iter())
+            .any(|suffix| word.ends_with(suffix))
+    {
+        Some("het")
+    } else if DUTCH_DE_SUFFIXES
+        .iter()
+        .any(|suffix| word.ends_with(suffix))
+    {
+        Some("de")
+    } else {
+        None
+    }
+}
+
+/// Whether a word keeps its case at the start of a sentence or within one: when it is written in
+/// mixed case, e.g. iPhone, or is a URL
+fn keeps_case(word: &str) -> bool {
+    word.chars().skip(1).any(char::is_uppercase) || word.contains("://")
+}
+
+/// Lowercase the first letter of a text that is used within a sentence, e.g. the description
+/// "American astronaut", unless its first word is an initialism, e.g. NASA or FBI, or keeps its
+/// case
+pub fn decapitalize(text: &str) -> String {
+    let first_word = text.split(' ').next().unwrap_or_default();
+    if is_initialism(first_word) || keeps_case(first_word) {
+        return text.to_string();
+    }
+    lowercase_first(text)
+}
+
+/// Capitalize the first word of a sentence, also after an opening quote, unless it keeps its
+/// case
+fn capitalize(text: &str) -> String {
+    let start = text.trim_start_matches(|c: char| !c.is_alphanumeric());
+    let first_word = start.split(' ').next().unwrap_or_default();
+    if keeps_case(first_word) {
+        return text.to_string();
+    }
+    format!(
+        "{}{}",
+        &text[..text.len() - start.len()],
+        uppercase_first(start)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(grammar: Grammar, template: &str, subject: &str, object: &str) -> String {
+        grammar
+            .render(template, &|slot: &str| match slot {
+                template::SUBJECT => Some(subject.to_string()),
+                template::OBJECT => Some(object.to_string()),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn english(object: &str) -> String {
+        render(Grammar::English, "{subject} is a {object}.", "It", object)
+    }
+
+    fn dutch(object: &str) -> String {
+        render(Grammar::Dutch, "Het is de {object}.", "", object)
+    }
+
+    #[test]
+    fn keeps_articles_of_labels_and_values() {
+        assert_eq!(
+            render(
+                Grammar::English,
+                "{subject} is a {object}.",
+                "A",
+                "letter of the Latin alphabet"
+            ),
+            "A is a letter of the Latin alphabet."
+        );
+        assert_eq!(
+            render(
+                Grammar::English,
+                "{subject} is an {object}.",
+                "A Escrava Isaura",
+                "telenovela"
+            ),
+            "A Escrava Isaura is a telenovela."
+        );
+        assert_eq!(
+            render(
+                Grammar::English,
+                "{subject} is {object}.",
+                "It",
+                "an university"
+            ),
+            "It is an university."
+        );
+        assert_eq!(
+            render(Grammar::Dutch, "{subject} is {object}.", "het", "de huis"),
+            "Het is de huis."
+        );
+        // Only articles directly before a slot, also in optional sections and at the start
+        assert_eq!(
+            render(
+                Grammar::English,
+                "A {object}[ is an {subject}].",
+                "ape",
+                "hour"
+            ),
+            "An hour is an ape."
+        );
+    }
+
+    #[test]
+    fn chooses_english_articles_by_sound() {
+        assert_eq!(english("university"), "It is a university.");
+        assert_eq!(english("European city"), "It is a European city.");
+        assert_eq!(english("one-way street"), "It is a one-way street.");
+        assert_eq!(english("UniProt entry"), "It is a UniProt entry.");
+        assert_eq!(english("hour"), "It is an hour.");
+        assert_eq!(english("honorary title"), "It is an honorary title.");
+        assert_eq!(english("apple"), "It is an apple.");
+        assert_eq!(english("\"open\" source"), "It is an \"open\" source.");
+        assert_eq!(
+            english("18th century house"),
+            "It is an 18th century house."
+        );
+        assert_eq!(
+            english("11,000 seat stadium"),
+            "It is an 11,000 seat stadium."
+        );
+        assert_eq!(english("110 m hurdler"), "It is a 110 m hurdler.");
+    }
+
+    #[test]
+    fn pronounces_initialisms_letter_by_letter() {
+        assert_eq!(english("U.S. state"), "It is a U.S. state.");
+        assert_eq!(english("NCAA team"), "It is an NCAA team.");
+        assert_eq!(english("NCBI identifier"), "It is an NCBI identifier.");
+        assert_eq!(english("SSRN paper"), "It is an SSRN paper.");
+        assert_eq!(english("NFL.com profile"), "It is an NFL.com profile.");
+        assert_eq!(english("UMLS concept"), "It is a UMLS concept.");
+        assert_eq!(english("X-ray"), "It is an X-ray.");
+        assert_eq!(english("FBI agent"), "It is an FBI agent.");
+        // Acronyms that are pronounced as a word
+        assert_eq!(english("NATO summit"), "It is a NATO summit.");
+        assert_eq!(english("NATO-led mission"), "It is a NATO-led mission.");
+        assert_eq!(english("NASA astronaut"), "It is a NASA astronaut.");
+        assert_eq!(english("FIFA World Cup"), "It is a FIFA World Cup.");
+        assert_eq!(english("LEGO set"), "It is a LEGO set.");
+        assert_eq!(english("SARS outbreak"), "It is a SARS outbreak.");
+        assert_eq!(english("OPEC member"), "It is an OPEC member.");
+        assert_eq!(english("UEFA competition"), "It is a UEFA competition.");
+        assert_eq!(english("UNESCO site"), "It is a UNESCO site.");
+    }
+
+    #[test]
+    fn chooses_dutch_articles_of_known_nouns() {
+        assert_eq!(dutch("schilderij"), "Het is het schilderij.");
+        assert_eq!(dutch("publiek"), "Het is het publiek.");
+        assert_eq!(dutch("ding"), "Het is het ding.");
+        assert_eq!(dutch("eiland"), "Het is het eiland.");
+        assert_eq!(dutch("realisme"), "Het is het realisme.");
+        assert_eq!(dutch("republiek"), "Het is de republiek.");
+        assert_eq!(dutch("verbinding"), "Het is de verbinding.");
+        assert_eq!(
+            render(Grammar::Dutch, "Het is het {object}.", "", "universiteit"),
+            "Het is de universiteit."
+        );
+        // Names and adjectives keep the article of the template
+        assert_eq!(dutch("Telegraaf"), "Het is de Telegraaf.");
+        assert_eq!(dutch("grootste stad"), "Het is de grootste stad.");
+    }
+
+    #[test]
+    fn decapitalizes_unless_case_is_kept() {
+        assert_eq!(decapitalize("American astronaut"), "american astronaut");
+        assert_eq!(decapitalize("NASA astronaut"), "NASA astronaut");
+        assert_eq!(decapitalize("U.S. state"), "U.S. state");
+        assert_eq!(decapitalize("iOS app"), "iOS app");
+        assert_eq!(decapitalize("PlayStation game"), "PlayStation game");
+        assert_eq!(decapitalize(""), "");
+    }
+
+    #[test]
+    fn capitalizes_the_first_word() {
+        assert_eq!(
+            render(
+                Grammar::Other,
+                "{subject} is {object}.",
+                "'s-Hertogenbosch",
+                "x"
+            ),
+            "'s-Hertogenbosch is x."
+        );
+        assert_eq!(
+            render(Grammar::Other, "{subject} is {object}.", "\"stad\"", "x"),
+            "\"Stad\" is x."
+        );
+        assert_eq!(
+            render(Grammar::Other, "{subject} is {object}.", "iPhone", "x"),
+            "iPhone is x."
+        );
+        assert_eq!(
+            render(
+                Grammar::Other,
+                "{subject} is {object}.",
+                "https://a.org",
+                "x"
+            ),
+            "https://a.org is x."
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::processing_error::ProcessingError;
+
+/// Thread-safe writer of length-prefixed MessagePack records
+pub struct IntermediateWriter {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl IntermediateWriter {
+    pub fn create(path: &Path) -> Result<Self, ProcessingError> {
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    /// Serializes the record outside the lock, and appends it to the file
+    pub fn write<T: Serialize>(&self, record: &T) -> Result<(), ProcessingError> {
+        let bytes = rmp_serde::to_vec(record)?;
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), ProcessingError> {
+        self.writer.into_inner().unwrap().flush()?;
+        Ok(())
+    }
+}
+
+/// Iterates over the raw MessagePack records of an intermediate file
+pub struct IntermediateReader {
+    reader: BufReader<File>,
+}
+
+impl IntermediateReader {
+    pub fn open(path: &Path) -> Result<Self, ProcessingError> {
+        Ok(Self {
+            reader: BufReader::new(File::open(path)?),
+        })
+    }
+}
+
+impl Iterator for IntermediateReader {
+    type Item = Result<Vec<u8>, ProcessingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut len = [0u8; 4];
+        match self.reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return None,
+            Err(e) => return Some(Err(e.into())),
+        }
+        let mut record = vec![0u8; u32::from_le_bytes(len) as usize];
+        Some(
+            self.reader
+                .read_exact(&mut record)
+                .map(|_| record)
+                .map_err(ProcessingError::from),
+        )
+    }
+}
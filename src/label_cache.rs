@@ -0,0 +1,683 @@
+use dashmap::DashMap;
+use memmap2::{Mmap, MmapOptions};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use crate::gender::Gender;
+use crate::processing_error::ProcessingError;
+use crate::utils::{fnv1a, FNV_OFFSET_BASIS};
+
+/// Magic bytes at the start of a label cache file
+const MAGIC: &[u8; 4] = b"WDLC";
+/// Magic bytes at the start of a class cache file
+const CLASS_MAGIC: &[u8; 4] = b"WDCC";
+/// Version of the cache file layouts
+const FORMAT_VERSION: u32 = 9;
+/// Maximum number of subclass of (P279) steps to a class of a class specific template
+const MAX_CLASS_DEPTH: usize = 16;
+/// Size of the fixed header: magic, version and length of the metadata
+const HEADER_SIZE: usize = 12;
+/// Number of evenly spaced samples of the dump that are hashed
+const HASH_SAMPLES: u64 = 16;
+/// Size of each hashed sample of the dump
+const HASH_SAMPLE_SIZE: u64 = 64 * 1024;
+
+/// Describes the dump and languages a cache was created from, so stale caches can be detected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheSource {
+    pub dump_path: String,
+    pub dump_size: u64,
+    /// Modification time of the dump in seconds since the Unix epoch
+    pub dump_modified: u64,
+    /// FNV-1a hash of evenly spaced samples of the dump
+    pub content_hash: u64,
+    /// Main language followed by its fallback languages
+    pub languages: Vec<String>,
+}
+
+impl CacheSource {
+    /// Describe the given dump and languages
+    pub fn from_dump(input_path: &str, languages: &[String]) -> Result<Self, ProcessingError> {
+        let dump_path = std::fs::canonicalize(input_path)?;
+        let mut file = File::open(&dump_path)?;
+        let metadata = file.metadata()?;
+        let dump_modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+
+        let mut hash = fnv1a(FNV_OFFSET_BASIS, &metadata.len().to_le_bytes());
+        let mut sample = Vec::with_capacity(HASH_SAMPLE_SIZE as usize);
+        for i in 0..HASH_SAMPLES {
+            let offset = metadata.len().saturating_sub(HASH_SAMPLE_SIZE) * i / (HASH_SAMPLES - 1);
+            file.seek(SeekFrom::Start(offset))?;
+            sample.clear();
+            Read::by_ref(&mut file)
+                .take(HASH_SAMPLE_SIZE)
+                .read_to_end(&mut sample)?;
+            hash = fnv1a(hash, &sample);
+        }
+
+        Ok(Self {
+            dump_path: dump_path.to_string_lossy().to_string(),
+            dump_size: metadata.len(),
+            dump_modified,
+            content_hash: hash,
+            languages: languages.to_vec(),
+        })
+    }
+
+    /// Human readable list of the differences with another source
+    pub fn differences(&self, other: &CacheSource) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.dump_path != other.dump_path {
+            differences.push(format!(
+                "dump path {} vs {}",
+                self.dump_path, other.dump_path
+            ));
+        }
+        if self.dump_size != other.dump_size {
+            differences.push(format!(
+                "dump size {} vs {}",
+                self.dump_size, other.dump_size
+            ));
+        }
+        if self.dump_modified != other.dump_modified {
+            differences.push(format!(
+                "dump modification time {} vs {}",
+                self.dump_modified, other.dump_modified
+            ));
+        }
+        if self.content_hash != other.content_hash {
+            differences.push("dump content".to_string());
+        }
+        if self.languages != other.languages {
+            differences.push(format!(
+                "languages {} vs {}",
+                self.languages.join(","),
+                other.languages.join(",")
+            ));
+        }
+        differences
+    }
+}
+
+/// Metadata stored after the fixed header of a label cache file
+#[derive(Serialize, Deserialize)]
+struct CacheMetadata {
+    source: CacheSource,
+    /// Symbols of units, e.g. km² for square kilometre
+    unit_symbols: HashMap<u32, String>,
+    /// Label of each property, with the index of its language in `source.languages`
+    property_labels: HashMap<u32, (u8, String)>,
+    /// Formatter URL (P1630) of each property with external identifiers
+    formatter_urls: HashMap<u32, String>,
+    /// Item of each language, by its Wikimedia language code
+    language_items: HashMap<String, u32>,
+}
+
+/// Labels, unit symbols and property details collected from the dump, to be saved to a cache file
+#[derive(Default)]
+pub struct CollectedLabels {
+    /// Label of each entity, with the index of its language in `CacheSource.languages`
+    pub labels: DashMap<u32, (u8, String)>,
+    /// Symbol (P5061) of each unit
+    pub unit_symbols: DashMap<u32, String>,
+    /// Label of each property, e.g. 1630 for P1630, with the index of its language
+    pub property_labels: DashMap<u32, (u8, String)>,
+    /// Formatter URL (P1630) of each property
+    pub formatter_urls: DashMap<u32, String>,
+    /// Item of each language, by its Wikimedia language code (P424), e.g. 5287 for `ja`
+    pub language_items: DashMap<String, u32>,
+    /// Female form (P2521) of labels, e.g. actress for actor, with the index of its language
+    pub female_labels: DashMap<u32, (u8, String)>,
+    /// Male form (P3321) of labels, with the index of its language
+    pub male_labels: DashMap<u32, (u8, String)>,
+}
+
+fn read_u32(bytes: &[u8], start: usize) -> u32 {
+    u32::from_le_bytes(bytes[start..start + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], start: usize) -> u64 {
+    u64::from_le_bytes(bytes[start..start + 8].try_into().unwrap())
+}
+
+/// Index of an id in the `count` sorted ids (`u32`) starting at `ids_start`, using a binary search
+fn find_id(bytes: &[u8], ids_start: usize, count: usize, id: u32) -> Option<usize> {
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match read_u32(bytes, ids_start + 4 * mid).cmp(&id) {
+            Ordering::Less => low = mid + 1,
+            Ordering::Greater => high = mid,
+            Ordering::Equal => return Some(mid),
+        }
+    }
+    None
+}
+
+fn truncated(path: &Path) -> ProcessingError {
+    ProcessingError::Other(format!("Truncated cache file: {:?}", path))
+}
+
+/// Map a cache file and check its header, returning the mapped file, its metadata and the
+/// position after the metadata
+fn map_file<T: DeserializeOwned>(
+    path: &Path,
+    magic: &[u8; 4],
+) -> Result<(Mmap, T, usize), ProcessingError> {
+    let file = File::open(path)?;
+    let mmap = unsafe { MmapOptions::new().map(&file)? };
+    if mmap.len() < HEADER_SIZE || &mmap[0..4] != magic {
+        return Err(ProcessingError::Other(format!(
+            "Not a cache file: {:?}",
+            path
+        )));
+    }
+    let version = read_u32(&mmap, 4);
+    if version != FORMAT_VERSION {
+        return Err(ProcessingError::Other(format!(
+            "Unsupported cache version {} in {:?}, please recreate the cache",
+            version, path
+        )));
+    }
+    let metadata_end = HEADER_SIZE + read_u32(&mmap, 8) as usize;
+    let metadata = rmp_serde::from_slice(
+        mmap.get(HEADER_SIZE..metadata_end)
+            .ok_or_else(|| truncated(path))?,
+    )?;
+    Ok((mmap, metadata, metadata_end))
+}
+
+/// Create a cache file and write its header and metadata
+fn create_file<T: Serialize>(
+    path: &Path,
+    magic: &[u8; 4],
+    metadata: &T,
+) -> Result<BufWriter<File>, ProcessingError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(magic)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    let metadata = rmp_serde::to_vec(metadata)?;
+    writer.write_all(&(metadata.len() as u32).to_le_bytes())?;
+    writer.write_all(&metadata)?;
+    Ok(writer)
+}
+
+/// Position of a table of labels in a mapped cache file. The table consists of the number of
+/// labels (`u64`), followed by the sorted entity ids (`u32`), the index of the language of each
+/// label in `source.languages` (`u8`), the offsets of each label in the string arena (`u64`, one
+/// more than the number of ids), and the arena itself.
+struct LabelTable {
+    count: usize,
+    ids_start: usize,
+    languages_start: usize,
+    offsets_start: usize,
+    arena_start: usize,
+}
+
+impl LabelTable {
+    /// Locate the table starting at `start`, and the position after its end
+    fn locate(bytes: &[u8], start: usize) -> Option<(Self, usize)> {
+        let count = read_u64(bytes.get(..start + 8)?, start) as usize;
+        let ids_start = start + 8;
+        let languages_start = ids_start + 4 * count;
+        let offsets_start = languages_start + count;
+        let arena_start = offsets_start + 8 * (count + 1);
+        if bytes.len() < arena_start {
+            return None;
+        }
+        let table = Self {
+            count,
+            ids_start,
+            languages_start,
+            offsets_start,
+            arena_start,
+        };
+        let end = arena_start + table.offset_at(bytes, count);
+        (bytes.len() >= end).then_some((table, end))
+    }
+
+    /// Write the labels as a table, sorted by id
+    fn write(
+        writer: &mut impl Write,
+        labels: DashMap<u32, (u8, String)>,
+    ) -> Result<(), ProcessingError> {
+        let mut entries: Vec<(u32, (u8, String))> = labels.into_iter().collect();
+        entries.sort_unstable_by_key(|(id, _)| *id);
+        writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+        for (id, _) in &entries {
+            writer.write_all(&id.to_le_bytes())?;
+        }
+        for (_, (language, _)) in &entries {
+            writer.write_all(&[*language])?;
+        }
+        let mut offset = 0u64;
+        writer.write_all(&offset.to_le_bytes())?;
+        for (_, (_, label)) in &entries {
+            offset += label.len() as u64;
+            writer.write_all(&offset.to_le_bytes())?;
+        }
+        for (_, (_, label)) in &entries {
+            writer.write_all(label.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn offset_at(&self, bytes: &[u8], index: usize) -> usize {
+        read_u64(bytes, self.offsets_start + 8 * index) as usize
+    }
+
+    /// Label of the entity with the given id, and the index of its language
+    fn get<'a>(&self, bytes: &'a [u8], id: u32) -> Option<(&'a str, usize)> {
+        let index = find_id(bytes, self.ids_start, self.count, id)?;
+        let start = self.arena_start + self.offset_at(bytes, index);
+        let end = self.arena_start + self.offset_at(bytes, index + 1);
+        let label = std::str::from_utf8(bytes.get(start..end)?).ok()?;
+        Some((label, bytes[self.languages_start + index] as usize))
+    }
+}
+
+/// Memory mapped label cache, resolving entity ids (e.g. 42 for Q42) to their label.
+///
+/// The file consists of a header and metadata, followed by the tables of the labels, their
+/// female forms and their male forms, see `LabelTable`. Lookups use a binary search on the mapped
+/// ids, so no load step is needed.
+pub struct LabelCache {
+    mmap: Mmap,
+    /// Dump and language the cache was created from
+    pub source: CacheSource,
+    unit_symbols: HashMap<u32, String>,
+    property_labels: HashMap<u32, (u8, String)>,
+    formatter_urls: HashMap<u32, String>,
+    language_items: HashMap<String, u32>,
+    labels: LabelTable,
+    female_labels: LabelTable,
+    male_labels: LabelTable,
+    /// Subclass relations, shared by the label caches of all languages
+    classes: Arc<ClassCache>,
+}
+
+impl LabelCache {
+    /// Open an existing cache file, using the given subclass relations
+    pub fn open(path: &Path, classes: Arc<ClassCache>) -> Result<Self, ProcessingError> {
+        println!("Loading existing cache file: {:?}", path);
+        let (mmap, metadata, start): (Mmap, CacheMetadata, usize) = map_file(path, MAGIC)?;
+        let (labels, start) = LabelTable::locate(&mmap, start).ok_or_else(|| truncated(path))?;
+        let (female_labels, start) =
+            LabelTable::locate(&mmap, start).ok_or_else(|| truncated(path))?;
+        let (male_labels, _) = LabelTable::locate(&mmap, start).ok_or_else(|| truncated(path))?;
+        Ok(Self {
+            mmap,
+            source: metadata.source,
+            unit_symbols: metadata.unit_symbols,
+            property_labels: metadata.property_labels,
+            formatter_urls: metadata.formatter_urls,
+            language_items: metadata.language_items,
+            labels,
+            female_labels,
+            male_labels,
+            classes,
+        })
+    }
+
+    /// Write the collected labels, unit symbols and property details to a cache file, sorting the labels by id
+    pub fn save(
+        path: &Path,
+        source: &CacheSource,
+        collected: CollectedLabels,
+    ) -> Result<(), ProcessingError> {
+        println!(
+            "Saving {} labels to cache file: {:?}",
+            collected.labels.len(),
+            path
+        );
+        let mut writer = create_file(
+            path,
+            MAGIC,
+            &CacheMetadata {
+                source: source.clone(),
+                unit_symbols: collected.unit_symbols.into_iter().collect(),
+                property_labels: collected.property_labels.into_iter().collect(),
+                formatter_urls: collected.formatter_urls.into_iter().collect(),
+                language_items: collected.language_items.into_iter().collect(),
+            },
+        )?;
+        LabelTable::write(&mut writer, collected.labels)?;
+        LabelTable::write(&mut writer, collected.female_labels)?;
+        LabelTable::write(&mut writer, collected.male_labels)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Symbol of the unit with the given id, e.g. km² for square kilometre
+    pub fn unit_symbol(&self, id: u32) -> Option<&str> {
+        self.unit_symbols.get(&id).map(String::as_str)
+    }
+
+    /// Label of the property with the given id, and the index of its language in
+    /// `source.languages`
+    pub fn property_label(&self, id: u32) -> Option<(&str, usize)> {
+        self.property_labels
+            .get(&id)
+            .map(|(language, label)| (label.as_str(), *language as usize))
+    }
+
+    /// Formatter URL of the property with the given id, e.g. `https://viaf.org/viaf/$1` for P214
+    pub fn formatter_url(&self, id: u32) -> Option<&str> {
+        self.formatter_urls.get(&id).map(String::as_str)
+    }
+
+    /// Item of the language with the given Wikimedia language code, e.g. 5287 for `ja`
+    pub fn language_item(&self, code: &str) -> Option<u32> {
+        self.language_items.get(code).copied()
+    }
+
+    /// Form of the label of the entity with the given id that agrees with a gender, e.g. actress
+    /// for actor, and the index of its language in `source.languages`
+    pub fn gendered_label(&self, id: u32, gender: Gender) -> Option<(&str, usize)> {
+        let labels = match gender {
+            Gender::Female => &self.female_labels,
+            Gender::Male => &self.male_labels,
+            Gender::NonBinary | Gender::Unknown => return None,
+        };
+        labels.get(&self.mmap, id)
+    }
+
+    /// Distance of each (indirect) superclass of the given classes, see
+    /// `ClassCache::class_distances`
+    pub fn class_distances(&self, classes: &[u32]) -> HashMap<u32, usize> {
+        self.classes.class_distances(classes)
+    }
+
+    /// Label of the entity with the given id
+    pub fn get(&self, id: u32) -> Option<&str> {
+        self.get_with_language(id).map(|(label, _)| label)
+    }
+
+    /// Label of the entity with the given id, and the index of its language in `source.languages`
+    pub fn get_with_language(&self, id: u32) -> Option<(&str, usize)> {
+        self.labels.get(&self.mmap, id)
+    }
+}
+
+/// Memory mapped subclass of (P279) relations, which do not depend on the language, so a single
+/// file is shared by the label caches of all languages.
+///
+/// The file consists of a header and metadata, followed by the number of classes (`u64`), the
+/// sorted class ids (`u32`), the offsets of the direct superclasses of each class in the
+/// superclass array (`u64`, one more than the number of ids), and the superclass array (`u32`).
+pub struct ClassCache {
+    mmap: Mmap,
+    /// Dump the cache was created from, without languages
+    pub source: CacheSource,
+    count: usize,
+    ids_start: usize,
+    offsets_start: usize,
+    superclasses_start: usize,
+}
+
+impl ClassCache {
+    /// Open an existing class cache file
+    pub fn open(path: &Path) -> Result<Self, ProcessingError> {
+        println!("Loading existing class cache file: {:?}", path);
+        let (mmap, source, start): (Mmap, CacheSource, usize) = map_file(path, CLASS_MAGIC)?;
+        let count = read_u64(mmap.get(..start + 8).ok_or_else(|| truncated(path))?, start) as usize;
+        let ids_start = start + 8;
+        let offsets_start = ids_start + 4 * count;
+        let superclasses_start = offsets_start + 8 * (count + 1);
+        if mmap.len() < superclasses_start
+            || mmap.len()
+                < superclasses_start + 4 * read_u64(&mmap, offsets_start + 8 * count) as usize
+        {
+            return Err(truncated(path));
+        }
+        Ok(Self {
+            mmap,
+            source,
+            count,
+            ids_start,
+            offsets_start,
+            superclasses_start,
+        })
+    }
+
+    /// Write the direct superclasses of each class to a class cache file, sorted by class
+    pub fn save(
+        path: &Path,
+        source: &CacheSource,
+        superclasses: DashMap<u32, Vec<u32>>,
+    ) -> Result<(), ProcessingError> {
+        println!(
+            "Saving the superclasses of {} classes to class cache file: {:?}",
+            superclasses.len(),
+            path
+        );
+        let mut entries: Vec<(u32, Vec<u32>)> = superclasses.into_iter().collect();
+        entries.sort_unstable_by_key(|(id, _)| *id);
+
+        let mut writer = create_file(path, CLASS_MAGIC, source)?;
+        writer.write_all(&(entries.len() as u64).to_le_bytes())?;
+        for (id, _) in &entries {
+            writer.write_all(&id.to_le_bytes())?;
+        }
+        let mut offset = 0u64;
+        writer.write_all(&offset.to_le_bytes())?;
+        for (_, superclasses) in &entries {
+            offset += superclasses.len() as u64;
+            writer.write_all(&offset.to_le_bytes())?;
+        }
+        for superclass in entries.iter().flat_map(|(_, superclasses)| superclasses) {
+            writer.write_all(&superclass.to_le_bytes())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Direct superclasses of a class, e.g. 486972 (human settlement) for 515 (city)
+    fn superclasses(&self, class: u32) -> impl Iterator<Item = u32> + '_ {
+        let range = find_id(&self.mmap, self.ids_start, self.count, class).map_or(0..0, |index| {
+            let offset_at = |index: usize| read_u64(&self.mmap, self.offsets_start + 8 * index);
+            offset_at(index) as usize..offset_at(index + 1) as usize
+        });
+        range.map(|index| read_u32(&self.mmap, self.superclasses_start + 4 * index))
+    }
+
+    /// Distance of each (indirect) superclass of the given classes, following the subclass of
+    /// (P279) relations up to `MAX_CLASS_DEPTH` steps, e.g. 1 for city and 2 for human settlement
+    /// when given million city. The given classes themselves have distance 0.
+    pub fn class_distances(&self, classes: &[u32]) -> HashMap<u32, usize> {
+        let mut distances: HashMap<u32, usize> = classes.iter().map(|class| (*class, 0)).collect();
+        let mut current = classes.to_vec();
+        for distance in 1..=MAX_CLASS_DEPTH {
+            let mut next = Vec::new();
+            for class in current {
+                for superclass in self.superclasses(class) {
+                    // Breadth first, so the first distance found is the shortest
+                    if let Entry::Vacant(entry) = distances.entry(superclass) {
+                        entry.insert(distance);
+                        next.push(superclass);
+                    }
+                }
+            }
+            if next.is_empty() {
+                break;
+            }
+            current = next;
+        }
+        distances
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Path of a cache file in the temporary directory, unique to the test
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("wdsdg-{}-{}.bin", std::process::id(), name))
+    }
+
+    fn source(languages: &[&str]) -> CacheSource {
+        CacheSource {
+            dump_path: "/data/latest-all.json.gz".to_string(),
+            dump_size: 1234,
+            dump_modified: 1700000000,
+            content_hash: 42,
+            languages: languages.iter().map(|lang| lang.to_string()).collect(),
+        }
+    }
+
+    /// Save the subclass relations to a class cache and open it
+    fn classes(name: &str, superclasses: &[(u32, &[u32])]) -> ClassCache {
+        let path = temp_path(name);
+        let collected: DashMap<u32, Vec<u32>> = superclasses
+            .iter()
+            .map(|(class, superclasses)| (*class, superclasses.to_vec()))
+            .collect();
+        ClassCache::save(&path, &source(&[]), collected).unwrap();
+        let classes = ClassCache::open(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        classes
+    }
+
+    fn collected() -> CollectedLabels {
+        let collected = CollectedLabels::default();
+        collected
+            .labels
+            .insert(42, (0, "Douglas Adams".to_string()));
+        collected.labels.insert(5, (1, "mens".to_string()));
+        collected.labels.insert(7, (0, String::new()));
+        collected.labels.insert(u32::MAX, (0, "last".to_string()));
+        collected.unit_symbols.insert(712226, "km²".to_string());
+        collected
+            .property_labels
+            .insert(1630, (0, "formatter URL".to_string()));
+        collected
+            .formatter_urls
+            .insert(214, "https://viaf.org/viaf/$1".to_string());
+        collected.language_items.insert("ja".to_string(), 5287);
+        collected
+            .female_labels
+            .insert(33999, (0, "actress".to_string()));
+        collected
+    }
+
+    #[test]
+    fn round_trips_labels() {
+        let path = temp_path("labels");
+        LabelCache::save(&path, &source(&["en", "nl"]), collected()).unwrap();
+        let cache = LabelCache::open(&path, Arc::new(classes("labels-classes", &[]))).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(cache.source, source(&["en", "nl"]));
+        assert_eq!(cache.get(42), Some("Douglas Adams"));
+        assert_eq!(cache.get_with_language(5), Some(("mens", 1)));
+        assert_eq!(cache.get(7), Some(""));
+        assert_eq!(cache.get(u32::MAX), Some("last"));
+        for missing in [0, 6, 43, u32::MAX - 1] {
+            assert_eq!(cache.get(missing), None);
+        }
+        assert_eq!(
+            cache.gendered_label(33999, Gender::Female),
+            Some(("actress", 0))
+        );
+        assert_eq!(cache.gendered_label(33999, Gender::Male), None);
+        assert_eq!(cache.gendered_label(33999, Gender::NonBinary), None);
+        assert_eq!(cache.gendered_label(42, Gender::Female), None);
+        assert_eq!(cache.unit_symbol(712226), Some("km²"));
+        assert_eq!(cache.property_label(1630), Some(("formatter URL", 0)));
+        assert_eq!(cache.formatter_url(214), Some("https://viaf.org/viaf/$1"));
+        assert_eq!(cache.language_item("ja"), Some(5287));
+        assert_eq!(cache.language_item("nl"), None);
+    }
+
+    #[test]
+    fn rejects_truncated_and_other_caches() {
+        let path = temp_path("truncated");
+        LabelCache::save(&path, &source(&["en"]), collected()).unwrap();
+        let classes = Arc::new(classes("truncated-classes", &[(515, &[486972])]));
+        let bytes = std::fs::read(&path).unwrap();
+
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(LabelCache::open(&path, classes.clone()).is_err());
+        std::fs::write(&path, &bytes[..HEADER_SIZE - 1]).unwrap();
+        assert!(LabelCache::open(&path, classes.clone()).is_err());
+
+        let mut other_version = bytes.clone();
+        other_version[4..8].copy_from_slice(&(FORMAT_VERSION - 1).to_le_bytes());
+        std::fs::write(&path, &other_version).unwrap();
+        assert!(LabelCache::open(&path, classes.clone()).is_err());
+
+        // A label cache is not a class cache
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(ClassCache::open(&path).is_err());
+        assert!(LabelCache::open(&path, classes).is_ok());
+        std::fs::remove_file(&path).unwrap();
+
+        let path = temp_path("truncated-class");
+        let superclasses = DashMap::new();
+        superclasses.insert(515, vec![486972, 2]);
+        ClassCache::save(&path, &source(&[]), superclasses).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(ClassCache::open(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn measures_superclass_distances() {
+        // Million city (1637706) is a city (515), which is a human settlement (486972) and a
+        // municipality (15284), which is a human settlement too. The cycle back to city must
+        // not loop.
+        let classes = classes(
+            "distances",
+            &[
+                (1637706, &[515]),
+                (515, &[486972, 15284]),
+                (15284, &[486972]),
+                (486972, &[515]),
+            ],
+        );
+        assert_eq!(
+            classes.class_distances(&[1637706]),
+            HashMap::from([(1637706, 0), (515, 1), (486972, 2), (15284, 2)])
+        );
+        assert_eq!(
+            classes.class_distances(&[15284, 1637706]),
+            HashMap::from([(15284, 0), (1637706, 0), (486972, 1), (515, 1)])
+        );
+        assert_eq!(classes.class_distances(&[5]), HashMap::from([(5, 0)]));
+        assert_eq!(classes.class_distances(&[]), HashMap::new());
+    }
+
+    #[test]
+    fn lists_source_differences() {
+        let cached = source(&["en", "nl"]);
+        assert!(cached.differences(&cached).is_empty());
+        let current = CacheSource {
+            dump_size: 4321,
+            content_hash: 43,
+            languages: vec!["en".to_string()],
+            ..cached.clone()
+        };
+        assert_eq!(
+            cached.differences(&current),
+            [
+                "dump size 1234 vs 4321",
+                "dump content",
+                "languages en,nl vs en"
+            ]
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::config::Config;
+use crate::label_cache::{CacheSource, ClassCache, LabelCache};
+use crate::processing_error::ProcessingError;
+use crate::properties::Properties;
+
+/// One of the languages that is generated, with its fallback chain and properties
+pub struct Language {
+    /// The language followed by its fallback languages
+    pub languages: Vec<String>,
+    /// Output directory of the language, e.g. `output/en`
+    pub output_dir: String,
+    pub properties: Properties,
+    /// Dump and languages the label cache of this language must be created from
+    pub source: CacheSource,
+}
+
+impl Language {
+    /// Load the properties of the language, and describe the dump its label cache is created from
+    pub fn load(lang: &str, input_path: &str, config: &Config) -> Result<Self, ProcessingError> {
+        let languages = config.languages(lang);
+        Ok(Self {
+            output_dir: format!("{}/{}", config.output_dir, lang),
+            properties: Properties::load(lang)?,
+            source: CacheSource::from_dump(input_path, &languages)?,
+            languages,
+        })
+    }
+
+    /// Path of the entity label cache of the language
+    pub fn cache_path(&self) -> PathBuf {
+        PathBuf::from(format!("{}/entity_cache.bin", self.output_dir))
+    }
+
+    /// Open the existing cache, refusing it when it was created from another dump or language
+    pub fn open_cache(&self, classes: &Arc<ClassCache>) -> Result<LabelCache, ProcessingError> {
+        let cache_file = self.cache_path();
+        let cache = LabelCache::open(&cache_file, classes.clone())?;
+        let differences = cache.source.differences(&self.source);
+        if !differences.is_empty() {
+            return Err(ProcessingError::Other(format!(
+                "The cache file {:?} does not match the input ({}), use --recreate_cache to rebuild it",
+                cache_file,
+                differences.join(", ")
+            )));
+        }
+        Ok(cache)
+    }
+}
@@ -0,0 +1,348 @@
+use dashmap::DashMap;
+use serde_json::Value;
+
+use crate::gender::Gender;
+use crate::grammar::Grammar;
+
+// Additional, manually added property keys
+const DATE_FORMAT: &str = "date_format";
+const MONTH_FORMAT: &str = "month_format";
+const MONTHS: &str = "months";
+const ORDINAL_SUFFIXES: &str = "ordinal_suffixes";
+const DECADE_FORMAT: &str = "decade_format";
+const CENTURY_FORMAT: &str = "century_format";
+const MILLENNIUM_FORMAT: &str = "millennium_format";
+const BCE_FORMAT: &str = "bce_format";
+const JULIAN_FORMAT: &str = "julian_format";
+const YEARS_AGO_FORMAT: &str = "years_ago_format";
+const LIST: &str = "list";
+const LIST_SEPARATOR: &str = "list_separator";
+const DIGIT_GROUP_SEPARATOR: &str = "digit_group_separator";
+const DECIMAL_SEPARATOR: &str = "decimal_separator";
+const APPROXIMATE_FORMAT: &str = "approximate_format";
+const MILLION: &str = "million";
+const BILLION: &str = "billion";
+const GLOBE_FORMAT: &str = "globe_format";
+const COMPASS_POINTS: &str = "compass_points";
+const MONOLINGUAL_FORMAT: &str = "monolingual_format";
+const MALE_PRONOUNS: &str = "male_pronouns";
+const FEMALE_PRONOUNS: &str = "female_pronouns";
+const NEUTRAL_PRONOUNS: &str = "neutral_pronouns";
+
+/// Numbers from this size onwards are rounded to millions or billions
+const ONE_MILLION: f64 = 1e6;
+const ONE_BILLION: f64 = 1e9;
+
+/// Calendar model of dates in the Julian calendar
+const JULIAN_CALENDAR: &str = "http://www.wikidata.org/entity/Q1985786";
+
+// Precision of a Wikidata time value
+const PRECISION_MILLENNIUM: u64 = 6;
+const PRECISION_CENTURY: u64 = 7;
+const PRECISION_DECADE: u64 = 8;
+const PRECISION_YEAR: u64 = 9;
+const PRECISION_MONTH: u64 = 10;
+const PRECISION_DAY: u64 = 11;
+
+/// Language specific formatting of dates, numbers and lists, based on the manually added rows
+/// of the properties file
+pub struct Locale {
+    /// Format of a date with day precision, e.g. `%B %-d, %Y`
+    pub date_format: String,
+    /// Format of a date with month precision, e.g. `%B %Y`
+    pub month_format: String,
+    /// Names of the months, January first
+    pub months: Vec<String>,
+    /// Suffixes of ordinal numbers ending in 1, 2, 3 and any other digit, e.g. st, nd, rd, th
+    pub ordinal_suffixes: Vec<String>,
+    /// Template of a decade, e.g. `{}s`
+    pub decade_format: String,
+    /// Template of a century, filled with its ordinal number, e.g. `{} century`
+    pub century_format: String,
+    /// Template of a millennium, filled with its ordinal number, e.g. `{} millennium`
+    pub millennium_format: String,
+    /// Template of a date before the common era, e.g. `{} BC`
+    pub bce_format: String,
+    /// Template of a date in the Julian calendar, e.g. `{} (Julian calendar)`
+    pub julian_format: String,
+    /// Template of a time before the common era beyond a millennium, e.g. `{} years ago`
+    pub years_ago_format: String,
+    /// Word used to join the last item of a list, e.g. "and"
+    pub and_symbol: String,
+    /// Separator of the other items of a list, e.g. ", "
+    pub list_separator: String,
+    /// Separator of each group of three digits, e.g. "," in 8,804,190
+    pub digit_group_separator: String,
+    /// Separator of the integer and fractional part of a number, e.g. "." in 1.65
+    pub decimal_separator: String,
+    /// Template of a rounded number, e.g. `about {}`
+    pub approximate_format: String,
+    /// Word for a million, e.g. "million"
+    pub million: String,
+    /// Word for a billion (10^9), e.g. "billion"
+    pub billion: String,
+    /// Template of coordinates on another globe than Earth, filled with the coordinates and the
+    /// globe, e.g. `{} (on {})`
+    pub globe_format: String,
+    /// Abbreviations of north, south, east and west, e.g. N, S, E, W
+    pub compass_points: Vec<String>,
+    /// Template of a text in another language, filled with the language and the text, e.g.
+    /// `(in {}: {})`
+    pub monolingual_format: String,
+    /// Subject, object and possessive pronoun of a man, e.g. he, him, his
+    pub male_pronouns: Vec<String>,
+    /// Subject, object and possessive pronoun of a woman, e.g. she, her, her
+    pub female_pronouns: Vec<String>,
+    /// Subject, object and possessive pronoun of anyone else, e.g. they, them, their
+    pub neutral_pronouns: Vec<String>,
+    /// Articles and capitalization of the language
+    pub grammar: Grammar,
+}
+
+impl Locale {
+    /// Read the formats from the property map, using English defaults for missing rows
+    pub fn load(lang: &str, property_map: &DashMap<String, (String, String, String)>) -> Self {
+        let get = |key: &str, default: &str| {
+            property_map
+                .get(key)
+                .map_or(default.to_string(), |entry| entry.value().1.clone())
+        };
+        let split = |value: String| value.split(',').map(|s| s.trim().to_string()).collect();
+        Self {
+            date_format: get(DATE_FORMAT, "%Y-%m-%d"),
+            month_format: get(MONTH_FORMAT, "%B %Y"),
+            months: split(get(
+                MONTHS,
+                "January,February,March,April,May,June,July,August,September,October,November,December",
+            )),
+            ordinal_suffixes: split(get(ORDINAL_SUFFIXES, "st,nd,rd,th")),
+            decade_format: get(DECADE_FORMAT, "{}s"),
+            century_format: get(CENTURY_FORMAT, "{} century"),
+            millennium_format: get(MILLENNIUM_FORMAT, "{} millennium"),
+            bce_format: get(BCE_FORMAT, "{} BC"),
+            julian_format: get(JULIAN_FORMAT, "{} (Julian calendar)"),
+            years_ago_format: get(YEARS_AGO_FORMAT, "{} years ago"),
+            and_symbol: get(LIST, "and"),
+            list_separator: get(LIST_SEPARATOR, ", "),
+            digit_group_separator: get(DIGIT_GROUP_SEPARATOR, ","),
+            decimal_separator: get(DECIMAL_SEPARATOR, "."),
+            approximate_format: get(APPROXIMATE_FORMAT, "about {}"),
+            million: get(MILLION, "million"),
+            billion: get(BILLION, "billion"),
+            globe_format: get(GLOBE_FORMAT, "{} (on {})"),
+            compass_points: split(get(COMPASS_POINTS, "N,S,E,W")),
+            monolingual_format: get(MONOLINGUAL_FORMAT, "(in {}: {})"),
+            male_pronouns: split(get(MALE_PRONOUNS, "he,him,his")),
+            female_pronouns: split(get(FEMALE_PRONOUNS, "she,her,her")),
+            neutral_pronouns: split(get(NEUTRAL_PRONOUNS, "they,them,their")),
+            grammar: Grammar::from_language(lang),
+        }
+    }
+
+    /// Subject, object and possessive pronoun of the given gender
+    pub fn pronouns(&self, gender: Gender) -> &[String] {
+        match gender {
+            Gender::Male => &self.male_pronouns,
+            Gender::Female => &self.female_pronouns,
+            Gender::NonBinary | Gender::Unknown => &self.neutral_pronouns,
+        }
+    }
+
+    /// Format a decimal number, such as the amount of a quantity, using the digit group and
+    /// decimal separators, e.g. 8,804,190 for `+8804190`. Other text is returned as is.
+    pub fn format_number(&self, number: &str) -> String {
+        let (sign, digits) = match number.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", number.trim_start_matches('+')),
+        };
+        let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if integer.is_empty()
+            || !integer.bytes().all(|b| b.is_ascii_digit())
+            || !fraction.bytes().all(|b| b.is_ascii_digit())
+        {
+            return number.to_string();
+        }
+
+        let mut formatted = sign.to_string();
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                formatted.push_str(&self.digit_group_separator);
+            }
+            formatted.push(digit);
+        }
+        if !fraction.is_empty() {
+            formatted.push_str(&self.decimal_separator);
+            formatted.push_str(fraction);
+        }
+        formatted
+    }
+
+    /// Ordinal number, e.g. 1st, 12th or 23rd in English
+    pub fn ordinal(&self, n: u64) -> String {
+        let index = match (n % 10, n % 100) {
+            (_, 11..=13) => 3,
+            (1, _) => 0,
+            (2, _) => 1,
+            (3, _) => 2,
+            _ => 3,
+        };
+        let suffix = self
+            .ordinal_suffixes
+            .get(index)
+            .or(self.ordinal_suffixes.last())
+            .map_or("", String::as_str);
+        format!("{}{}", n, suffix)
+    }
+
+    /// Render the value of a Wikidata time datavalue according to its precision and calendar
+    /// model, e.g. "5th century BC", "1970s", "March 1848" or a full date
+    pub fn format_time(&self, value: &Value) -> Option<String> {
+        let (year, month, day) = parse_time(value["time"].as_str()?)?;
+        let precision = value["precision"].as_u64().unwrap_or(PRECISION_DAY);
+        // Fall back to a lower precision when the month or day is unknown
+        let precision = match (month, day) {
+            (0, _) => precision.min(PRECISION_YEAR),
+            (_, 0) => precision.min(PRECISION_MONTH),
+            _ => precision,
+        };
+        let bce = year < 0;
+        let year = year.unsigned_abs();
+
+        let text = match precision {
+            // Geological and astronomical times, e.g. 13.8 billion years ago, where an era is
+            // meaningless
+            0..PRECISION_MILLENNIUM if bce => {
+                return Some(
+                    self.years_ago_format
+                        .replacen("{}", &self.format_years(year), 1),
+                );
+            }
+            0..PRECISION_MILLENNIUM => self.format_years(year),
+            PRECISION_MILLENNIUM => self.millennium_format.replacen(
+                "{}",
+                &self.ordinal(year.saturating_sub(1) / 1000 + 1),
+                1,
+            ),
+            PRECISION_CENTURY => self.century_format.replacen(
+                "{}",
+                &self.ordinal(year.saturating_sub(1) / 100 + 1),
+                1,
+            ),
+            PRECISION_DECADE => self
+                .decade_format
+                .replacen("{}", &(year / 10 * 10).to_string(), 1),
+            PRECISION_YEAR => year.to_string(),
+            PRECISION_MONTH => self.format_date(&self.month_format, year, month, day),
+            _ => self.format_date(&self.date_format, year, month, day),
+        };
+        let text = if bce {
+            self.bce_format.replacen("{}", &text, 1)
+        } else {
+            text
+        };
+        let julian = value["calendarmodel"].as_str() == Some(JULIAN_CALENDAR);
+        if julian && precision >= PRECISION_MONTH {
+            Some(self.julian_format.replacen("{}", &text, 1))
+        } else {
+            Some(text)
+        }
+    }
+
+    /// Large number of years, rounded to millions or billions, e.g. "13.8 billion", or with
+    /// digit groups, e.g. "100,000"
+    fn format_years(&self, years: u64) -> String {
+        self.format_scaled(years as f64)
+            .map_or_else(|| self.format_number(&years.to_string()), |(text, _)| text)
+    }
+
+    /// Number of at least a million, rounded to millions or billions, with a decimal below a
+    /// hundred of them, e.g. "8.8 million" or "2 million", and whether the rounding is exact
+    pub fn format_scaled(&self, value: f64) -> Option<(String, bool)> {
+        let (scale, word) = match value.abs() {
+            v if v >= ONE_BILLION => (ONE_BILLION, &self.billion),
+            v if v >= ONE_MILLION => (ONE_MILLION, &self.million),
+            _ => return None,
+        };
+        let scaled = value / scale;
+        let decimals = if scaled.abs() < 100.0 { 1 } else { 0 };
+        let rounded = format!("{:.*}", decimals, scaled);
+        let exact = (rounded.parse::<f64>().ok()? * scale - value).abs() < 0.5;
+        // Drop a redundant fraction, e.g. 2.0 million
+        let rounded = rounded.strip_suffix(".0").unwrap_or(&rounded);
+        Some((format!("{} {}", self.format_number(rounded), word), exact))
+    }
+
+    /// Format a date using `%Y` (year), `%m` and `%-m` (month number), `%d` and `%-d` (day) and
+    /// `%B` (month name). Unlike chrono, any year is supported.
+    fn format_date(&self, format: &str, year: u64, month: u32, day: u32) -> String {
+        let month_name = self
+            .months
+            .get(month.saturating_sub(1) as usize)
+            .map_or("", String::as_str);
+        format
+            .replace("%Y", &year.to_string())
+            .replace("%-m", &month.to_string())
+            .replace("%m", &format!("{:02}", month))
+            .replace("%-d", &day.to_string())
+            .replace("%d", &format!("{:02}", day))
+            .replace("%B", month_name)
+    }
+}
+
+/// Parse the year, month and day of a Wikidata time, e.g. `+1848-03-00T00:00:00Z` or
+/// `-13798000000-00-00T00:00:00Z`
+fn parse_time(time: &str) -> Option<(i64, u32, u32)> {
+    let (negative, time) = match time.strip_prefix('-') {
+        Some(time) => (true, time),
+        None => (false, time.trim_start_matches('+')),
+    };
+    let date = time.split('T').next()?;
+    let mut parts = date.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month = parts.next().map_or(Some(0), |m| m.parse().ok())?;
+    let day = parts.next().map_or(Some(0), |d| d.parse().ok())?;
+    Some((if negative { -year } else { year }, month, day))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn time(time: &str, precision: u64) -> Value {
+        json!({
+            "time": time,
+            "precision": precision,
+            "calendarmodel": "http://www.wikidata.org/entity/Q1985727",
+        })
+    }
+
+    #[test]
+    fn formats_time_by_precision() {
+        let locale = Locale::load("en", &DashMap::new());
+        let format = |value: Value| locale.format_time(&value).unwrap();
+        assert_eq!(format(time("+1929-06-12T00:00:00Z", 11)), "1929-06-12");
+        assert_eq!(format(time("+1848-03-00T00:00:00Z", 11)), "March 1848");
+        assert_eq!(format(time("+1975-00-00T00:00:00Z", 8)), "1970s");
+        assert_eq!(format(time("-0500-00-00T00:00:00Z", 7)), "5th century BC");
+        assert_eq!(format(time("+2000-00-00T00:00:00Z", 6)), "2nd millennium");
+    }
+
+    #[test]
+    fn formats_deep_time_in_years_ago() {
+        let locale = Locale::load("en", &DashMap::new());
+        let format = |value: Value| locale.format_time(&value).unwrap();
+        assert_eq!(
+            format(time("-13798000000-00-00T00:00:00Z", 3)),
+            "13.8 billion years ago"
+        );
+        assert_eq!(
+            format(time("-66000000-00-00T00:00:00Z", 4)),
+            "66 million years ago"
+        );
+        assert_eq!(
+            format(time("-100000-00-00T00:00:00Z", 5)),
+            "100,000 years ago"
+        );
+    }
+}
@@ -1,564 +1,519 @@
+use dashmap::DashMap;
 use rayon::iter::{ParallelBridge, ParallelIterator};
-use serde::Deserialize;
-use serde_json::{Map, Value};
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
-use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::fs::{create_dir_all, remove_file};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use utils::{format_coordinates, format_date, generate_text, lowercase_first};
 
-use dashmap::DashMap;
-use memmap2::MmapOptions;
+mod aligned;
+use aligned::{aligned_header, write_aligned, ALIGNED_DIR};
 
 mod csv_writer_pool;
 use csv_writer_pool::CsvWriterPool;
 
 mod utils;
 
+mod dump_reader;
+use dump_reader::{for_each_line, for_each_line_from};
+
+mod checkpoint;
+use checkpoint::Checkpoint;
+
+mod entity;
+use entity::{ExtractedEntity, RenderedEntity, WikidataEntity};
+
+mod coordinate;
+mod gender;
+mod grammar;
+mod label_cache;
+mod locale;
+mod quantity;
+use label_cache::{CacheSource, ClassCache, CollectedLabels, LabelCache};
+
+mod language;
+use language::Language;
+
+mod intermediate;
+use intermediate::{IntermediateReader, IntermediateWriter};
+
+mod properties;
+
+mod template;
+
+mod shutdown;
+use shutdown::{check_shutdown, clear_partial, install_handler, is_partial, mark_partial};
+
 mod processing_error;
 use processing_error::ProcessingError;
 mod config;
 use config::{get_configuration, Config};
 
-#[derive(Deserialize, Debug)]
-struct Sitelink {
-    // title: String,               // The title of the page on the specific site
-    // badges: Option<Vec<String>>, // Optional badges associated with the link
-    // url: Option<String>,         // Optional URL of the page
-}
-
-#[derive(Debug, Deserialize)]
-struct WikidataEntity {
-    id: String,
-    claims: Option<Map<String, Value>>,
-    labels: Option<Map<String, Value>>,
-    descriptions: Option<Map<String, Value>>,
-    aliases: Option<Map<String, Value>>,
-    // #[serde(default)]
-    sitelinks: Option<HashMap<String, Sitelink>>,
-}
-
-// Extracted progress printing for reusability
-fn print_progress(start_time: Instant, current_promille: u64) {
-    let elapsed = start_time.elapsed();
-    let eta = if current_promille > 0 {
-        let total_estimated_time = elapsed.as_secs_f64() / (current_promille as f64 / 1000.0);
-        Duration::from_secs_f64(total_estimated_time - elapsed.as_secs_f64())
-    } else {
-        Duration::from_secs(0)
-    };
-
-    print!(
-        "\rProcessing: {:.1}% | Elapsed: {:.0}s | ETA: {:.0}s         ",
-        current_promille as f64 / 10.0,
-        elapsed.as_secs(),
-        eta.as_secs()
+/// Column names of the output of each language
+const OUTPUT_HEADER: [&str; 4] = ["label", "sentences", "questions", "label_languages"];
+
+/// Write a rendered entity to the CSV file of its category
+fn write_rendered(
+    csv_writers: &CsvWriterPool,
+    extracted: &ExtractedEntity,
+    rendered: &RenderedEntity,
+) {
+    csv_writers.write(
+        &rendered.category,
+        &[
+            &extracted.label,
+            &rendered.sentences(),
+            &rendered.questions(),
+            &rendered.label_languages,
+        ],
     );
-    std::io::stdout().flush().ok();
 }
 
+/// Collect the labels of all entities in each language, and save them to the label caches
 fn prefill_cache(
-    input_path: &String,
+    input_path: &str,
     config: &Config,
-) -> Result<DashMap<u32, String>, ProcessingError> {
-    let output_file = PathBuf::from(format!(
-        "{}/{}/entity_cache.csv",
-        config.output_dir, config.lang,
-    ));
-    
-    let entity_map = DashMap::new();
-    
-    if !config.recreate_cache && Path::new(&output_file).exists() {
-        println!("Loading existing cache file: {:?}", output_file);
-        let mut reader = csv::Reader::from_path(output_file)?;
-        for result in reader.records() {
-            let record = result?;
-            if !record[0].starts_with("Q") {
-                continue;
-            }
-            match record[0].replace("Q", "").parse::<u32>() {
-                Ok(key) => {
-                    entity_map.insert(key, record[1].to_string());
-                }
-                Err(_) => {
-                    println!("Failed to parse key: {:?}", record);
-                    continue;
-                }
-            }
-        }
-        entity_map.shrink_to_fit();
-        return Ok(entity_map);
+    languages: &[Language],
+) -> Result<Vec<LabelCache>, ProcessingError> {
+    for language in languages {
+        println!("Prefilling cache file: {:?}", language.cache_path());
     }
-    println!("Prefilling cache file: {:?}", output_file);
-    
-    // Open input file and get total file size for progress tracking
-    let file = File::open(input_path).expect("JSON dump file not found");
-    let file_size = file.metadata()?.len();
-    let mmap = unsafe { MmapOptions::new().map(&file)? };
-    let reader = BufReader::new(mmap.as_ref());
-    // let reader = BufReader::new(file);
-
-    // Progress tracking
-    let start_time = Instant::now();
-    let total_processed = AtomicU64::new(0);
-    let last_reported_promille = AtomicU64::new(0);
+
+    let collected: Vec<CollectedLabels> = languages
+        .iter()
+        .map(|_| CollectedLabels::default())
+        .collect();
+    let superclasses = DashMap::new();
 
     // Parallel processing with better error handling
-    reader
-        .lines()
-        .par_bridge()
-        .try_for_each(|line_result| -> Result<(), ProcessingError> {
-            let line: String = match line_result {
-                Ok(line) => line,
-                Err(e) => return Err(ProcessingError::IoError(e)),
-            };
+    for_each_line(input_path, |json_str| -> Result<(), ProcessingError> {
+        let entity: WikidataEntity = match serde_json::from_str(json_str) {
+            Ok(e) => e,
+            Err(_) => return Ok(()),
+        };
 
-            if line.trim().is_empty() || line.starts_with(['[', ']']) {
-                return Ok(());
-            }
+        collect_labels(&entity, languages, &collected, &superclasses);
 
-            let line_len = line.len() as u64;
-            let current_total = total_processed.fetch_add(line_len, Ordering::Relaxed) + line_len;
-            let current_promille = ((current_total as f64 / file_size as f64) * 1000.0) as u64;
+        Ok(())
+    })?;
 
-            if (current_promille - last_reported_promille.load(Ordering::Relaxed)) >= 1 {
-                last_reported_promille.store(current_promille, Ordering::Relaxed);
-                print_progress(start_time, current_promille);
-            }
+    save_caches(input_path, config, languages, collected, superclasses)
+}
 
-            let json_str = line.trim_end_matches(',');
-            let entity: WikidataEntity = match serde_json::from_str(json_str) {
-                Ok(e) => e,
-                Err(_) => return Ok(()),
-            };
-            if !entity.id.starts_with('Q') {
-                // println!("{:?}", entity);
-                return Ok(());
+/// Add the label, its female and male forms, unit symbol and language code of the entity, or the label and
+/// formatter URL of a property, in each language to the labels collected for that language, and
+/// the superclasses of the entity to those of all languages
+fn collect_labels(
+    entity: &WikidataEntity,
+    languages: &[Language],
+    collected: &[CollectedLabels],
+    superclasses: &DashMap<u32, Vec<u32>>,
+) {
+    if let Some(key) = entity.property_id() {
+        for (language, collected) in languages.iter().zip(collected) {
+            if let Some((index, label)) = entity.label(&language.languages) {
+                collected
+                    .property_labels
+                    .insert(key, (index as u8, label.to_string()));
             }
-
-            if let Some(label) = entity
-                .labels
-                .and_then(|labels| labels.get(&config.lang).cloned())
-                .and_then(|label_obj| label_obj.get("value")?.as_str().map(|s| s.to_string()))
-            {
-                // println!("{}, {}", entity.id, &label);
-                let key = entity.id.replace("Q", "").parse::<u32>().unwrap();
-                entity_map.insert(key, label);
+            if let Some(url) = entity.formatter_url() {
+                collected.formatter_urls.insert(key, url.to_string());
             }
+        }
+        return;
+    }
+    let Some(key) = entity.numeric_id() else {
+        return;
+    };
+    let classes = entity.superclasses();
+    if !classes.is_empty() {
+        superclasses.insert(key, classes);
+    }
+    for (language, collected) in languages.iter().zip(collected) {
+        if let Some((index, label)) = entity.label(&language.languages) {
+            collected
+                .labels
+                .insert(key, (index as u8, label.to_string()));
+        }
+        if let Some(symbol) = entity.unit_symbol(&language.languages) {
+            collected.unit_symbols.insert(key, symbol.to_string());
+        }
+        if let Some(code) = entity.language_code() {
+            collected.language_items.insert(code.to_string(), key);
+        }
+        if let Some((index, label)) = entity.female_label(&language.languages) {
+            collected
+                .female_labels
+                .insert(key, (index as u8, label.to_string()));
+        }
+        if let Some((index, label)) = entity.male_label(&language.languages) {
+            collected
+                .male_labels
+                .insert(key, (index as u8, label.to_string()));
+        }
+    }
+}
 
-            Ok(())
-        })?;
-
-    // Clear progress line
-    println!(
-        "\rProcessing: 100% | Completed in {:.0}s                 ",
-        start_time.elapsed().as_secs()
-    );
+/// Write the collected labels and superclasses to the cache files at the end, and use their
+/// memory mapped versions
+fn save_caches(
+    input_path: &str,
+    config: &Config,
+    languages: &[Language],
+    collected: Vec<CollectedLabels>,
+    superclasses: DashMap<u32, Vec<u32>>,
+) -> Result<Vec<LabelCache>, ProcessingError> {
+    let class_file = config.class_cache_path();
+    ClassCache::save(
+        &class_file,
+        &CacheSource::from_dump(input_path, &[])?,
+        superclasses,
+    )?;
+    let classes = Arc::new(ClassCache::open(&class_file)?);
+    languages
+        .iter()
+        .zip(collected)
+        .map(|(language, collected)| {
+            let cache_file = language.cache_path();
+            LabelCache::save(&cache_file, &language.source, collected)?;
+            LabelCache::open(&cache_file, classes.clone())
+        })
+        .collect()
+}
 
-    // Write resolver to CSV at the end
-    let mut writer = csv::Writer::from_path(output_file)?;
-    for entry in entity_map.iter() {
-        writer.write_record(&[&format!("Q{}", entry.key()), entry.value()])?;
+/// Output directories of the languages, followed by the one of the aligned output
+fn output_dirs(config: &Config, languages: &[Language]) -> Vec<String> {
+    let mut output_dirs: Vec<String> = languages
+        .iter()
+        .map(|language| language.output_dir.clone())
+        .collect();
+    if config.aligned {
+        output_dirs.push(format!("{}/{}", config.output_dir, ALIGNED_DIR));
     }
-    writer.flush()?;
+    output_dirs
+}
 
-    entity_map.shrink_to_fit();
-    Ok(entity_map)
+/// Flag the output as incomplete until the run succeeds, also in case of a crash or panic. Called
+/// right before the output files are truncated or appended, so a run that fails before, e.g.
+/// on a stale cache, leaves complete output unflagged.
+fn mark_output_partial(
+    input_path: &str,
+    config: &Config,
+    languages: &[Language],
+) -> Result<(), ProcessingError> {
+    for output_dir in output_dirs(config, languages) {
+        mark_partial(&output_dir, &format!("Incomplete run of {}", input_path))?;
+    }
+    Ok(())
 }
 
-// WikiProperties record
-#[derive(Debug, Deserialize)]
-struct WikiProperties {
-    key: String,
-    value: String,
-    sentence: String,
-    question: String,
+/// Open the existing class cache, refusing it when it was created from another dump
+fn open_class_cache(input_path: &str, config: &Config) -> Result<Arc<ClassCache>, ProcessingError> {
+    let cache_file = config.class_cache_path();
+    let cache = ClassCache::open(&cache_file)?;
+    let differences = cache
+        .source
+        .differences(&CacheSource::from_dump(input_path, &[])?);
+    if !differences.is_empty() {
+        return Err(ProcessingError::Other(format!(
+            "The cache file {:?} does not match the input ({}), use --recreate_cache to rebuild it",
+            cache_file,
+            differences.join(", ")
+        )));
+    }
+    Ok(Arc::new(cache))
 }
 
 fn process_wikidata(
-    input_path: &String,
+    input_path: &str,
     config: &Config,
-    resolver: DashMap<u32, String>,
+    languages: &[Language],
+    resolvers: Vec<LabelCache>,
 ) -> Result<(), ProcessingError> {
-    // Additional, manually added property keys
-    const DESCRIPTIONS: &str = "descriptions";
-    const PERSON_DESCRIPTIONS: &str = "person_descriptions";
-    const ALIASES: &str = "aliases";
-    const PERSON_ALIASES: &str = "person_aliases";
-    // const MALE_PERSON: &str = "male_person";
-    // const FEMALE_PERSON: &str = "female_person";
-    const LIST: &str = "list";
-    const DATE_FORMAT: &str = "date_format";
-    // const MISSING_DATE: &str = "missing_date";
-
-    let properties_file = PathBuf::from(format!("./data/wikidata-{}-properties.csv", config.lang,));
-    println!(
-        "Processing Wikidata using properties and input file: {:?}, {}",
-        properties_file, input_path
-    );
-
-    let csv_writers = CsvWriterPool::new(&format!("{}/{}", config.output_dir, config.lang));
-
-    let property_map = DashMap::new();
+    println!("Processing Wikidata using input file: {}", input_path);
 
-    let mut reader = csv::ReaderBuilder::new()
-        .delimiter(b';')
-        .has_headers(true)
-        .from_reader(File::open(properties_file)?);
-    for result in reader.deserialize() {
-        // println!("Record: {:?}", result);
-        let record: WikiProperties = result?;
-        property_map.insert(
-            record.key.to_string(),
+    let checkpoint = if config.resume {
+        Checkpoint::load(&config.output_dir)?
+    } else {
+        None
+    };
+    let aligned_dir = format!("{}/{}", config.output_dir, ALIGNED_DIR);
+    let aligned_header = aligned_header(&config.langs);
+    let (all_csv_writers, aligned_writers, start_offset) = match checkpoint {
+        Some(checkpoint)
+            if checkpoint.input_file == input_path
+                && checkpoint.langs == config.langs
+                && checkpoint.aligned == config.aligned =>
+        {
+            println!("Resuming from offset {}", checkpoint.offset);
+            mark_output_partial(input_path, config, languages)?;
             (
-                record.value.to_string(),    // value
-                record.sentence.to_string(), // sentence
-                record.question.to_string(), // question
-            ),
-        );
-    }
-
-    let and_symbol = property_map
-        .get(LIST)
-        .map_or("and".to_string(), |entry| entry.value().1.clone());
-    let date_format = property_map
-        .get(DATE_FORMAT)
-        .map_or("%Y-%m-%d".to_string(), |entry| entry.value().1.clone());
-    // let missing_date = property_map
-    //     .get(MISSING_DATE)
-    //     .map_or("missing date".to_string(), |entry| entry.value().1.clone());
-
-    // Open input file and get total file size for progress tracking
-    let file = File::open(input_path).expect("JSON dump file not found");
-    let file_size = file.metadata()?.len();
-    let mmap = unsafe { MmapOptions::new().map(&file)? };
-    let reader = BufReader::new(mmap.as_ref());
-    // let reader = BufReader::new(file);
-
-    // Progress tracking
-    let start_time = Instant::now();
-    let total_processed = AtomicU64::new(0);
-    let last_reported_promille = AtomicU64::new(0);
-
-    // Parallel processing with better error handling
-    reader
-        .lines()
-        .par_bridge()
-        .try_for_each(|line_result| -> Result<(), ProcessingError> {
-            let line: String = match line_result {
-                Ok(line) => line,
-                Err(e) => return Err(ProcessingError::IoError(e)),
-            };
-
-            if line.trim().is_empty() || line.starts_with(['[', ']']) {
-                return Ok(());
+                languages
+                    .iter()
+                    .map(|language| {
+                        CsvWriterPool::resume(
+                            &language.output_dir,
+                            &OUTPUT_HEADER,
+                            &checkpoint.files,
+                        )
+                    })
+                    .collect::<Result<Vec<CsvWriterPool>, _>>()?,
+                config
+                    .aligned
+                    .then(|| {
+                        CsvWriterPool::resume(&aligned_dir, &aligned_header, &checkpoint.files)
+                    })
+                    .transpose()?,
+                checkpoint.offset,
+            )
+        }
+        Some(checkpoint) => {
+            return Err(ProcessingError::Other(format!(
+            "Checkpoint belongs to a different input file, languages or aligned output: {} ({}{})",
+            checkpoint.input_file,
+            checkpoint.langs.join(","),
+            if checkpoint.aligned { ", aligned" } else { "" }
+        )))
+        }
+        None => {
+            if config.resume {
+                println!("No checkpoint found, starting from the beginning");
             }
+            mark_output_partial(input_path, config, languages)?;
+            (
+                languages
+                    .iter()
+                    .map(|language| CsvWriterPool::new(&language.output_dir, &OUTPUT_HEADER))
+                    .collect(),
+                config
+                    .aligned
+                    .then(|| CsvWriterPool::new(&aligned_dir, &aligned_header)),
+                0,
+            )
+        }
+    };
 
-            let line_len = line.len() as u64;
-            let current_total = total_processed.fetch_add(line_len, Ordering::Relaxed) + line_len;
-            let current_promille = ((current_total as f64 / file_size as f64) * 1000.0) as u64;
-
-            if (current_promille - last_reported_promille.load(Ordering::Relaxed)) >= 1 {
-                last_reported_promille.store(current_promille, Ordering::Relaxed);
-                print_progress(start_time, current_promille);
-            }
+    let checkpoint_interval = Duration::from_secs(config.checkpoint_interval);
+    let mut last_checkpoint = Instant::now();
 
-            let json_str = line.trim_end_matches(',');
+    // Parallel processing with better error handling
+    let result = for_each_line_from(
+        input_path,
+        start_offset,
+        |json_str| -> Result<(), ProcessingError> {
             let entity: WikidataEntity = match serde_json::from_str(json_str) {
                 Ok(e) => e,
                 Err(_) => return Ok(()),
             };
 
-            // Process entity
-
-            if let (
-                Some(claims),
-                Some(labels),
-                Some(descriptions),
-                Some(aliases),
-                // Some(sitelinks),
-            ) = (
-                entity.claims,
-                entity.labels,
-                entity.descriptions,
-                entity.aliases,
-                // entity.sitelinks,
-            ) {
-                if let Some(label_obj) = labels.get(&config.lang) {
-                    if let Some(label) = label_obj.get("value").and_then(|v| v.as_str()) {
-                        let mut sentences: Vec<String> = Vec::new();
-                        let mut questions: Vec<String> = Vec::new();
-
-                        let instance_of = claims.get("P31").and_then(|p31| p31.as_array()).map_or(
-                            Vec::new(),
-                            |instances| {
-                                instances
-                                    .iter()
-                                    .filter_map(|i| {
-                                        i["mainsnak"]["datavalue"]["value"]["numeric-id"]
-                                            .as_number()
-                                            .map(|instance| instance.as_u64().unwrap_or(0))
-                                    })
-                                    .collect()
-                            },
-                        );
-
-                        // println!(
-                        //     "{}: {}, desc: {}, instance_of {:?}, claims: {:?}\n\n",
-                        //     entity.id, label, description, instance_of, claims,
-                        // );
-
-                        // Q5 Human, Q15632617 Fictional Human
-                        let (male, female) = if instance_of.contains(&5)
-                            || instance_of.contains(&15632617)
-                        {
-                            let is_famous =
-                                entity.sitelinks.is_some() && !entity.sitelinks.unwrap().is_empty();
-                            if !is_famous {
-                                return Ok(()); // Skip non-famous humans.
-                            }
-                            //.contains_key("enwiki") {
-                            // Famous human, having at least one wikipage in his/her name.
-                            let gender = claims.get("P21").and_then(|p21| p21.as_array()).map_or(
-                                Vec::new(),
-                                |genders| {
-                                    genders
-                                        .iter()
-                                        .filter_map(|i| {
-                                            i["mainsnak"]["datavalue"]["value"]["id"]
-                                                .as_str()
-                                                .map(|gender| gender.to_string())
-                                        })
-                                        .collect()
-                                },
-                            );
-                            let male = gender.contains(&"Q6581097".to_string());
-                            let female = gender.contains(&"Q6581072".to_string());
-
-                            // println!("Entity: {}", entity.id);
-                            // println!("Label: {}", label);
-                            // println!("Desc: {}\n\n", description);
-                            // println!("Has English Wiki: {}", sitelinks.get("enwiki").);
-                            // println!("{:?}", sitelinks);
-                            // println!(
-                            //     "{}: {}, instance_of {:?}, part_of {:?}, sitelinks: {:?}",
-                            //     label, description, instance_of, part_of, sitelinks
-                            // )
-                            (Some(male), Some(female))
-                        } else {
-                            (None, None)
-                        };
-                        let is_human = male.is_some() || female.is_some();
-
-                        let description = lowercase_first(descriptions
-                            .get(&config.lang)
-                            .and_then(|obj| obj.get("value"))
-                            .and_then(|v| v.as_str())
-                            .unwrap_or(""));
-
-                        if !description.is_empty() {
-                            let prop_key = if is_human { PERSON_DESCRIPTIONS } else { DESCRIPTIONS };
-                            generate_text(&property_map, &mut sentences, &mut questions, &and_symbol, prop_key, label, &vec![&description]);
-                        }
-
-                        let aliases = aliases
-                            .get(&config.lang)
-                            .and_then(|value| value.as_array())
-                            .and_then(|values| {
-                                // dbg!(&values);
-                                Some(
-                                    values
-                                        .iter()
-                                        .map(|v| {
-                                            v.get("value").and_then(|v| v.as_str()).unwrap_or("")
-                                        })
-                                        .filter(|alias| *alias != label)
-                                        .collect::<Vec<&str>>(),
-                                )
-                            })
-                            .unwrap_or(Vec::new());
-                        // println!("{}: {}", label, alias_str);
-                        if !aliases.is_empty() {
-                            let prop_key = if is_human { PERSON_ALIASES } else { ALIASES };
-                            generate_text(&property_map, &mut sentences, &mut questions, &and_symbol, prop_key, label, &aliases);
-                        }
-
-                        // Process all claims
-                        for (prop_key, value) in &claims {
-                            let prop_value = value.as_array().map_or(Vec::new(), |instances| {
-                                instances
-                                    .iter()
-                                    .filter_map(|i| {
-                                        let media_type =
-                                            i["mainsnak"]["datavalue"]["type"].as_str();
-
-                                        match media_type.map(|instance| instance) {
-                                            Some("wikibase-entityid") => {
-                                                match i["mainsnak"]["datavalue"]["value"]
-                                                    ["numeric-id"]
-                                                    .as_number()
-                                                    .map(|instance| instance.as_u64().unwrap_or(0))
-                                                {
-                                                    Some(id) => {
-                                                        resolver.get(&(id as u32)).map(|r| r.clone())
-                                                    }
-                                                    // _ => Some(i["mainsnak"]["datavalue"]["value"]["numeric-id"].as_number().to_string()),
-                                                    _ => None,
-                                                }
-                                            }
-                                            Some("external-id") => i["mainsnak"]["datavalue"]
-                                                ["value"]
-                                                .as_str()
-                                                .map(|instance| instance.to_string()),
-                                            Some("globecoordinate") => {
-                                                let lat = i["mainsnak"]["datavalue"]["value"]
-                                                    ["latitude"]
-                                                    .as_number()
-                                                    .map(|instance| instance.to_string());
-                                                let lon = i["mainsnak"]["datavalue"]["value"]
-                                                    ["longitude"]
-                                                    .as_number()
-                                                    .map(|instance| instance.to_string());
-                                                let alt = i["mainsnak"]["datavalue"]["value"]
-                                                    ["altitude"]
-                                                    .as_number()
-                                                    .map(|instance| instance.to_string());
-                                                // println!("{}: {:?}, {:?}, {:?}", label, i["mainsnak"]["datavalue"]["value"], lat, lon);
-                                                if lat.is_some() && lon.is_some() {
-                                                    Some(format_coordinates(
-                                                        lat.unwrap().as_str(),
-                                                        lon.unwrap().as_str(),
-                                                        alt.as_deref(),
-                                                    ))
-                                                } else {
-                                                    None
-                                                }
-                                            }
-                                            Some("quantity") => {
-                                                let amount = i["mainsnak"]["datavalue"]["value"]
-                                                    ["amount"]
-                                                    .as_str()
-                                                    .map(|instance| instance.to_string());
-                                                let unit = i["mainsnak"]["datavalue"]["value"]
-                                                    ["unit"]
-                                                    .as_str()
-                                                    .map(|instance| instance.to_string());
-                                                if let (Some(amount), Some(unit)) = (amount, unit) {
-                                                    match unit.as_str() {
-                                                        "1" => Some(amount),
-                                                        _ if unit.starts_with(
-                                                            "http://www.wikidata.org/entity/Q",
-                                                        ) =>
-                                                        {
-                                                            match unit.replace(
-                                                                "http://www.wikidata.org/entity/Q",
-                                                                "",
-                                                            ).parse::<u32>() {
-                                                                Ok(id) => {
-                                                                    let x = resolver.get(&id).map(|r| r.clone());
-                                                                    let result = Some(format!(
-                                                                        "{} {}",
-                                                                        amount,
-                                                                        if x.is_some() {
-                                                                            x.unwrap()
-                                                                        } else {
-                                                                            unit
-                                                                        }
-                                                                    ));
-                                                                    // println!("{}: {:?} - {:?}", label, id, result);
-                                                                    result
-                                                                }
-                                                                _ => None
-                                                            }
-                                                        }
-                                                        _ => None,
-                                                    }
-                                                } else {
-                                                    None
-                                                }
-                                            }
-                                            Some("monolingualtext") => i["mainsnak"]["datavalue"]
-                                                ["value"]["text"]
-                                                .as_str()
-                                                .map(|instance| instance.to_string()),
-                                            Some("time") => {
-                                                let time = i["mainsnak"]["datavalue"]["value"]
-                                                    ["time"]
-                                                    .as_str()
-                                                    .map(|instance| instance.to_string());
-                                                if time.is_some() {
-                                                    Some(format_date(
-                                                        time.unwrap().as_str(),
-                                                        &date_format,
-                                                    ))
-                                                } else {
-                                                    None
-                                                    // Some(missing_date.clone())
-                                                }
-                                            }
-                                            Some("string") => i["mainsnak"]["datavalue"]["value"]
-                                                .as_str()
-                                                .map(|instance| instance.to_string()),
-                                            Some("commonsMedia") =>  i["mainsnak"]["datavalue"]["value"]
-                                                .as_str()
-                                                .map(|instance| instance.to_string()),
-                                            None => None,
-                                            _ => i["mainsnak"]["datavalue"]["value"]
-                                                .as_str()
-                                                .map(|instance| instance.to_string()),
-                                        }
-                                    })
-                                    .collect()
-                            });
-                            if !prop_value.is_empty() {
-                                generate_text(&property_map, &mut sentences, &mut questions, &and_symbol, prop_key, label, &prop_value);
-                            }
-                        }
-
-                       
-                        // println!("{}: {}", label, category);
-                        // println!("{}: Sentences: {}", label, sentences.join("\n"));
-                        // println!("{}: Questions: {}", label, questions.join("\n"));
-                        // println!("{}: {:?}", category, instance_of);
-                        if !sentences.is_empty() || !questions.is_empty() {
-                            let category = instance_of
-                                .first()
-                                .map(|key| *key as u32)
-                                .and_then(|key| resolver.get(&key).map(|r| r.value().clone())) // Ensure ownership
-                                .unwrap_or_else(|| "misc".to_string()); // Ensure the fallback is owned
-                             csv_writers.write(
-                                &category,
-                                &[label, &sentences.join("\n"), &questions.join("\n")],
-                            );
-                        }
-                    }
+            // Parse once, render in every language
+            let extracted: Vec<Option<ExtractedEntity>> = languages
+                .iter()
+                .map(|language| {
+                    ExtractedEntity::from_entity(
+                        &entity,
+                        &language.languages,
+                        &language.properties,
+                        config,
+                    )
+                })
+                .collect();
+            let rendered: Vec<Option<RenderedEntity>> = extracted
+                .iter()
+                .zip(languages.iter().zip(&resolvers))
+                .map(|(extracted, (language, resolver))| {
+                    extracted.as_ref().and_then(|extracted| {
+                        extracted.render(&language.properties, resolver, config)
+                    })
+                })
+                .collect();
+            for ((extracted, rendered), csv_writers) in
+                extracted.iter().zip(&rendered).zip(&all_csv_writers)
+            {
+                if let (Some(extracted), Some(rendered)) = (extracted, rendered) {
+                    write_rendered(csv_writers, extracted, rendered);
                 }
             }
+            if let Some(aligned_writers) = &aligned_writers {
+                write_aligned(aligned_writers, &entity.id, &rendered);
+            }
 
             Ok(())
-        })?;
+        },
+        |offset| -> Result<(), ProcessingError> {
+            if last_checkpoint.elapsed() >= checkpoint_interval {
+                let mut files = HashMap::new();
+                for csv_writers in all_csv_writers.iter().chain(&aligned_writers) {
+                    files.extend(csv_writers.flush_and_measure()?);
+                }
+                Checkpoint {
+                    input_file: input_path.to_string(),
+                    langs: config.langs.clone(),
+                    aligned: config.aligned,
+                    offset,
+                    files,
+                }
+                .save(&config.output_dir)?;
+                last_checkpoint = Instant::now();
+            }
+            Ok(())
+        },
+    );
 
-    csv_writers.flush_all();
+    // Also flush the output when interrupted, so a resumed run can continue from the checkpoint
+    for csv_writers in all_csv_writers.iter().chain(&aligned_writers) {
+        csv_writers.flush_all();
+    }
+    result?;
+    Checkpoint::remove(&config.output_dir)?;
 
-    // Clear progress line
+    Ok(())
+}
+
+/// Process the dump in a single scan: the labels are collected while the extracted entities,
+/// with their references to other entities still unresolved, are written to a compact
+/// intermediate file per language. Next, the intermediate files are rendered using the
+/// collected labels.
+fn process_wikidata_single_pass(
+    input_path: &str,
+    config: &Config,
+    languages: &[Language],
+) -> Result<(), ProcessingError> {
+    if config.resume {
+        return Err(ProcessingError::Other(
+            "Resuming is not supported in single pass mode".to_string(),
+        ));
+    }
+    if config.aligned {
+        return Err(ProcessingError::Other(
+            "Aligned output is not supported in single pass mode".to_string(),
+        ));
+    }
     println!(
-        "\rProcessing: 100% | Completed in {:.0}s                 ",
-        start_time.elapsed().as_secs()
+        "Processing Wikidata in a single pass using input file: {}",
+        input_path
     );
 
+    let intermediate_files: Vec<PathBuf> = languages
+        .iter()
+        .map(|language| PathBuf::from(format!("{}/entities.msgpack", language.output_dir)))
+        .collect();
+    let intermediates = intermediate_files
+        .iter()
+        .map(|path| IntermediateWriter::create(path))
+        .collect::<Result<Vec<IntermediateWriter>, _>>()?;
+
+    let collected: Vec<CollectedLabels> = languages
+        .iter()
+        .map(|_| CollectedLabels::default())
+        .collect();
+    let superclasses = DashMap::new();
+
+    for_each_line(input_path, |json_str| -> Result<(), ProcessingError> {
+        let entity: WikidataEntity = match serde_json::from_str(json_str) {
+            Ok(e) => e,
+            Err(_) => return Ok(()),
+        };
+
+        collect_labels(&entity, languages, &collected, &superclasses);
+        for (language, intermediate) in languages.iter().zip(&intermediates) {
+            if let Some(extracted) = ExtractedEntity::from_entity(
+                &entity,
+                &language.languages,
+                &language.properties,
+                config,
+            ) {
+                intermediate.write(&extracted)?;
+            }
+        }
+
+        Ok(())
+    })?;
+    for intermediate in intermediates {
+        intermediate.finish()?;
+    }
+
+    let resolvers = save_caches(input_path, config, languages, collected, superclasses)?;
+    mark_output_partial(input_path, config, languages)?;
+
+    for ((language, resolver), intermediate_file) in
+        languages.iter().zip(&resolvers).zip(&intermediate_files)
+    {
+        println!(
+            "Resolving labels using intermediate file: {:?}",
+            intermediate_file
+        );
+        let csv_writers = CsvWriterPool::new(&language.output_dir, &OUTPUT_HEADER);
+        let result = IntermediateReader::open(intermediate_file)?
+            .par_bridge()
+            .try_for_each(|record| -> Result<(), ProcessingError> {
+                check_shutdown()?;
+                let extracted: ExtractedEntity = rmp_serde::from_slice(&record?)?;
+                if let Some(rendered) = extracted.render(&language.properties, resolver, config) {
+                    write_rendered(&csv_writers, &extracted, &rendered);
+                }
+                Ok(())
+            });
+        csv_writers.flush_all();
+        result?;
+
+        remove_file(intermediate_file)?;
+    }
     Ok(())
 }
 
 // fn main() -> Result<(), ProcessingError> {
 fn main() -> Result<(), ProcessingError> {
     let (input_file, config) = get_configuration()?;
+    install_handler()?;
+
+    let languages = config
+        .langs
+        .iter()
+        .map(|lang| Language::load(lang, &input_file, &config))
+        .collect::<Result<Vec<Language>, _>>()?;
 
-    let resolver = match prefill_cache(&input_file, &config) {
-        Ok(r) => r,
-        Err(e) => return Err(e),
+    let output_dirs = output_dirs(&config, &languages);
+    for output_dir in &output_dirs {
+        create_dir_all(output_dir)?;
+    }
+
+    let caches_exist = config.class_cache_path().exists()
+        && languages
+            .iter()
+            .all(|language| language.cache_path().exists());
+    let result = if !config.recreate_cache && caches_exist {
+        open_class_cache(&input_file, &config)
+            .and_then(|classes| {
+                languages
+                    .iter()
+                    .map(|language| language.open_cache(&classes))
+                    .collect::<Result<Vec<LabelCache>, _>>()
+            })
+            .and_then(|resolvers| process_wikidata(&input_file, &config, &languages, resolvers))
+    } else if config.single_pass {
+        process_wikidata_single_pass(&input_file, &config, &languages)
+    } else {
+        prefill_cache(&input_file, &config, &languages)
+            .and_then(|resolvers| process_wikidata(&input_file, &config, &languages, resolvers))
     };
-    process_wikidata(&input_file, &config, resolver)
-    // Ok(())
+
+    match result {
+        Ok(()) => {
+            for output_dir in &output_dirs {
+                clear_partial(output_dir)?;
+            }
+            Ok(())
+        }
+        Err(ProcessingError::Interrupted) => {
+            // Output that was not touched yet, e.g. when interrupted while collecting labels, is
+            // left as it was
+            for output_dir in output_dirs.iter().filter(|dir| is_partial(dir)) {
+                mark_partial(output_dir, &format!("Interrupted run of {}", input_file))?;
+                eprintln!("Interrupted: the output in {} is incomplete", output_dir);
+            }
+            std::process::exit(130);
+        }
+        Err(e) => Err(e),
+    }
 }
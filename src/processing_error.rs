@@ -1,11 +1,15 @@
 // Implement a custom error type that is Send + Sync
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum ProcessingError {
     IoError(std::io::Error),
     JsonError(serde_json::Error),
     CsvError(csv::Error),
     MessagePackError(rmp_serde::encode::Error),
-    // Other(String),
+    MessagePackDecodeError(rmp_serde::decode::Error),
+    /// Processing stopped after a shutdown signal
+    Interrupted,
+    Other(String),
 }
 
 impl std::fmt::Display for ProcessingError {
@@ -15,7 +19,11 @@ impl std::fmt::Display for ProcessingError {
             ProcessingError::JsonError(e) => write!(f, "JSON Error: {}", e),
             ProcessingError::CsvError(e) => write!(f, "CSV Error: {}", e),
             ProcessingError::MessagePackError(e) => write!(f, "MessagePack Error: {}", e),
-            // ProcessingError::Other(e) => write!(f, "Processing Error: {}", e),
+            ProcessingError::MessagePackDecodeError(e) => {
+                write!(f, "MessagePack Decode Error: {}", e)
+            }
+            ProcessingError::Interrupted => write!(f, "Interrupted"),
+            ProcessingError::Other(e) => write!(f, "Processing Error: {}", e),
         }
     }
 }
@@ -45,3 +53,9 @@ impl From<rmp_serde::encode::Error> for ProcessingError {
         ProcessingError::MessagePackError(error)
     }
 }
+
+impl From<rmp_serde::decode::Error> for ProcessingError {
+    fn from(error: rmp_serde::decode::Error) -> Self {
+        ProcessingError::MessagePackDecodeError(error)
+    }
+}
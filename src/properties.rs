@@ -0,0 +1,230 @@
+use dashmap::DashMap;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+
+use crate::gender::Gender;
+use crate::locale::Locale;
+use crate::processing_error::ProcessingError;
+use crate::template::qualifier_slots;
+use crate::utils::fnv1a;
+
+// Additional, manually added property keys
+/// Prefix of the keys of qualifier templates, e.g. `qualifier_P580` for start time
+const QUALIFIER_PREFIX: &str = "qualifier_";
+/// Suffixes of the keys of templates for several values, in order of preference: a sentence
+/// with the number of values, e.g. `P40#count`, or a plural sentence, e.g. `P26#plural`
+const NUMBER_VARIANTS: [&str; 2] = ["count", "plural"];
+/// Separator of the class of a class specific template, e.g. `P17@Q515` for cities
+const CLASS_SEPARATOR: char = '@';
+/// Suffix of a class whose template also applies to its subclasses, e.g. `P17@Q486972+`
+const SUBCLASSES_SUFFIX: char = '+';
+// const MISSING_DATE: &str = "missing_date";
+
+// WikiProperties record
+#[derive(Debug, Deserialize)]
+struct WikiProperties {
+    key: String,
+    value: String,
+    sentence: String,
+    question: String,
+    /// Wikibase datatype of the property, e.g. `external-id`, when known
+    #[serde(default)]
+    datatype: String,
+}
+
+/// Class that a template of a property is scoped to
+pub struct ClassScope {
+    /// Key of the template without gender or number suffix, e.g. `P17@Q486972+`
+    pub key: String,
+    /// Numeric id of the class, e.g. 486972 for Q486972 (human settlement)
+    pub class: u32,
+    /// Whether the template also applies to instances of (indirect) subclasses of the class
+    pub subclasses: bool,
+}
+
+impl ClassScope {
+    /// Property and class scope of a class specific key, e.g. P17 and Q486972 with its subclasses
+    /// for `P17@Q486972+` or `P17@Q486972+#plural`
+    fn parse(key: &str) -> Option<(&str, Self)> {
+        let (property, rest) = key.split_once(CLASS_SEPARATOR)?;
+        let scope = rest.split('#').next()?;
+        let class = scope.strip_suffix(SUBCLASSES_SUFFIX);
+        Some((
+            property,
+            ClassScope {
+                key: format!("{}{}{}", property, CLASS_SEPARATOR, scope),
+                class: class.unwrap_or(scope).strip_prefix('Q')?.parse().ok()?,
+                subclasses: class.is_some(),
+            },
+        ))
+    }
+}
+
+/// Language specific sentence and question templates for each Wikibase property
+pub struct Properties {
+    /// Property key mapped to its (value, sentence, question) templates, from its first row
+    pub property_map: DashMap<String, (String, String, String)>,
+    /// (sentence, question) templates of all rows of each key, where an empty column of a
+    /// further row is filled from the first row, e.g. to vary only the sentence
+    templates: HashMap<String, Vec<(String, String)>>,
+    /// Keys of the qualifiers referenced by the templates of each property, including its
+    /// class, gender and number specific ones
+    template_qualifiers: HashMap<String, Vec<String>>,
+    /// Datatype of each property that has one in the properties file
+    datatypes: HashMap<String, String>,
+    /// Classes of the class specific templates of each property
+    class_scopes: HashMap<String, Vec<ClassScope>>,
+    /// Formatting of dates, numbers and lists
+    pub locale: Locale,
+}
+
+impl Properties {
+    /// Load the properties CSV file of the given language, e.g. `./data/wikidata-en-properties.csv`
+    pub fn load(lang: &str) -> Result<Self, ProcessingError> {
+        let properties_file = PathBuf::from(format!("./data/wikidata-{}-properties.csv", lang));
+        println!("Loading properties file: {:?}", properties_file);
+
+        let property_map = DashMap::new();
+        let mut datatypes = HashMap::new();
+        let mut templates: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        let mut class_scopes: HashMap<String, Vec<ClassScope>> = HashMap::new();
+
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(b';')
+            .has_headers(true)
+            // The datatype column is optional, also per row
+            .flexible(true)
+            .from_reader(File::open(properties_file)?);
+        let headers = reader.headers()?.clone();
+        for result in reader.records() {
+            // println!("Record: {:?}", result);
+            let mut record = result?;
+            // Rows may leave out the trailing datatype
+            while record.len() < headers.len() {
+                record.push_field("");
+            }
+            let record: WikiProperties = record.deserialize(Some(&headers))?;
+            if !record.datatype.is_empty() {
+                datatypes.insert(record.key.clone(), record.datatype);
+            }
+            if let Some((property, scope)) = ClassScope::parse(&record.key) {
+                let scopes = class_scopes.entry(property.to_string()).or_default();
+                if !scopes.iter().any(|existing| existing.key == scope.key) {
+                    scopes.push(scope);
+                }
+            }
+            // Further rows of a key are variants of its templates
+            if let Some(rows) = templates.get_mut(&record.key) {
+                let (sentence, question) = &rows[0];
+                let or_first = |variant: String, first: &String| {
+                    if variant.is_empty() {
+                        first.clone()
+                    } else {
+                        variant
+                    }
+                };
+                let variant = (
+                    or_first(record.sentence, sentence),
+                    or_first(record.question, question),
+                );
+                rows.push(variant);
+                continue;
+            }
+            templates.insert(
+                record.key.clone(),
+                vec![(record.sentence.clone(), record.question.clone())],
+            );
+            property_map.insert(
+                record.key.to_string(),
+                (
+                    record.value.to_string(),    // value
+                    record.sentence.to_string(), // sentence
+                    record.question.to_string(), // question
+                ),
+            );
+        }
+
+        let mut template_qualifiers: HashMap<String, Vec<String>> = HashMap::new();
+        for (key, rows) in &templates {
+            // The templates of e.g. P40#female#count and P17@Q515 belong to P40 and P17
+            let property = key.split(['#', CLASS_SEPARATOR]).next().unwrap_or(key);
+            let qualifiers = template_qualifiers.entry(property.to_string()).or_default();
+            for (sentence, question) in rows {
+                for slot in qualifier_slots(sentence).chain(qualifier_slots(question)) {
+                    if !qualifiers.iter().any(|qualifier| qualifier == slot) {
+                        qualifiers.push(slot.to_string());
+                    }
+                }
+            }
+        }
+
+        let locale = Locale::load(lang, &property_map);
+        // let missing_date = property_map
+        //     .get(MISSING_DATE)
+        //     .map_or("missing date".to_string(), |entry| entry.value().1.clone());
+
+        Ok(Self {
+            property_map,
+            templates,
+            template_qualifiers,
+            datatypes,
+            class_scopes,
+            locale,
+        })
+    }
+
+    /// Datatype of a property according to the properties file, e.g. `external-id` for P214
+    pub fn datatype(&self, key: &str) -> Option<&str> {
+        self.datatypes.get(key).map(String::as_str)
+    }
+
+    /// Classes of the class specific templates of a property, in the order of the properties file
+    pub fn class_scopes(&self, key: &str) -> &[ClassScope] {
+        self.class_scopes.get(key).map_or(&[], Vec::as_slice)
+    }
+
+    /// Keys of the templates of a property for a subject of the given gender and the given number
+    /// of values, in order of preference, e.g. `P40#female#count`, `P40#count`,
+    /// `P40#female#plural`, `P40#plural`, `P40#female` and `P40` for a woman with several children
+    pub fn template_keys(key: &str, gender: Option<Gender>, count: usize) -> Vec<String> {
+        let gender = gender.and_then(|gender| gender.variant());
+        let numbers: &[&str] = if count > 1 { &NUMBER_VARIANTS } else { &[] };
+        let mut keys = Vec::new();
+        for number in numbers {
+            if let Some(gender) = gender {
+                keys.push(format!("{}#{}#{}", key, gender, number));
+            }
+            keys.push(format!("{}#{}", key, number));
+        }
+        if let Some(gender) = gender {
+            keys.push(format!("{}#{}", key, gender));
+        }
+        keys.push(key.to_string());
+        keys
+    }
+
+    /// Keys of the qualifiers referenced by the templates of a property, including its class,
+    /// gender and number specific ones, e.g. P580 for `{qualifier.P580}`
+    pub fn template_qualifiers(&self, key: &str) -> &[String] {
+        self.template_qualifiers.get(key).map_or(&[], Vec::as_slice)
+    }
+
+    /// (sentence, question) templates of a key, e.g. P569. When the properties file has several
+    /// rows for the key, one of them is picked based on the seed and the key, so the same seed
+    /// always picks the same row, while different properties pick their rows independently.
+    pub fn templates(&self, key: &str, seed: u64) -> Option<(&str, &str)> {
+        let templates = self.templates.get(key)?;
+        let index = fnv1a(seed, key.as_bytes()) % templates.len() as u64;
+        let (sentence, question) = &templates[index as usize];
+        Some((sentence, question))
+    }
+
+    /// Template of a qualifier, e.g. "from {}" for P580 (start time), when it should be rendered
+    pub fn qualifier_template(&self, key: &str) -> Option<String> {
+        self.property_map
+            .get(&format!("{}{}", QUALIFIER_PREFIX, key))
+            .map(|entry| entry.value().1.clone())
+    }
+}
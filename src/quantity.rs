@@ -0,0 +1,235 @@
+use serde_json::Value;
+
+use crate::config::UnitSystem;
+use crate::entity::{ClaimValue, ENTITY_URL_PREFIX};
+use crate::locale::Locale;
+
+/// Metric unit, its imperial counterpart, and the factor and offset that convert the former to
+/// the latter
+const CONVERSIONS: [(u32, u32, f64, f64); 10] = [
+    (11573, 3710, 3.28084, 0.0),     // metre to foot
+    (828224, 253276, 0.621371, 0.0), // kilometre to mile
+    (174728, 218593, 0.393701, 0.0), // centimetre to inch
+    (11570, 100995, 2.20462, 0.0),   // kilogram to pound
+    (41803, 48013, 0.035274, 0.0),   // gram to ounce
+    (712226, 232291, 0.386102, 0.0), // square kilometre to square mile
+    (25343, 857027, 10.7639, 0.0),   // square metre to square foot
+    (35852, 81292, 2.47105, 0.0),    // hectare to acre
+    (25267, 42289, 1.8, 32.0),       // degree Celsius to degree Fahrenheit
+    (180154, 211256, 0.621371, 0.0), // kilometre per hour to mile per hour
+];
+
+/// Decimal number, remembering the number of decimals it was given with
+#[derive(Clone, Copy)]
+struct Amount {
+    value: f64,
+    decimals: usize,
+}
+
+impl Amount {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim_start_matches('+');
+        let decimals = text
+            .split_once('.')
+            .map_or(0, |(_, fraction)| fraction.len());
+        Some(Self {
+            value: text.parse().ok()?,
+            decimals,
+        })
+    }
+
+    /// Number of significant digits, e.g. 3 for 1.65 and 5 for 219.32
+    fn significant_digits(&self) -> i32 {
+        (magnitude(self.value) + 1 + self.decimals as i32).max(1)
+    }
+
+    /// Convert the amount, keeping its number of significant digits
+    fn convert(&self, factor: f64, offset: f64) -> Self {
+        let value = self.value * factor + offset;
+        let decimals = (self.significant_digits() - 1 - magnitude(value)).max(0) as usize;
+        Self { value, decimals }
+    }
+
+    /// Value after rounding to the number of decimals
+    fn rounded(&self) -> f64 {
+        format!("{:.*}", self.decimals, self.value)
+            .parse()
+            .unwrap_or(self.value)
+    }
+
+    fn format(&self, locale: &Locale) -> String {
+        locale.format_number(&format!("{:.*}", self.decimals, self.value))
+    }
+}
+
+/// Power of ten of the most significant digit, e.g. 2 for 219.32
+fn magnitude(value: f64) -> i32 {
+    if value == 0.0 {
+        0
+    } else {
+        value.abs().log10().floor() as i32
+    }
+}
+
+/// Convert the unit to the requested unit system, returning the factor and offset to apply
+fn conversion(unit: u32, unit_system: UnitSystem) -> Option<(u32, f64, f64)> {
+    CONVERSIONS
+        .iter()
+        .find_map(|&(metric, imperial, factor, offset)| match unit_system {
+            UnitSystem::Imperial if unit == metric => Some((imperial, factor, offset)),
+            UnitSystem::Metric if unit == imperial => {
+                Some((metric, 1.0 / factor, -offset / factor))
+            }
+            _ => None,
+        })
+}
+
+/// Large numbers rounded to millions or billions, e.g. "about 8.8 million" for 8804190
+fn format_large_number(amount: &Amount, locale: &Locale) -> Option<String> {
+    let (text, exact) = locale.format_scaled(amount.value)?;
+    if exact {
+        Some(text)
+    } else {
+        Some(locale.approximate_format.replacen("{}", &text, 1))
+    }
+}
+
+/// Format the amount with its uncertainty, e.g. "12 ± 2" or "12 (+3/−1)"
+fn format_with_bounds(
+    amount: &Amount,
+    lower: Option<Amount>,
+    upper: Option<Amount>,
+    locale: &Locale,
+) -> String {
+    let text = amount.format(locale);
+    let (Some(lower), Some(upper)) = (lower, upper) else {
+        return text;
+    };
+    let decimals = amount.decimals.max(lower.decimals).max(upper.decimals);
+    let above = Amount {
+        value: upper.value - amount.value,
+        decimals,
+    };
+    let below = Amount {
+        value: amount.value - lower.value,
+        decimals,
+    };
+    let (above_text, below_text) = (above.format(locale), below.format(locale));
+    if above_text == below_text {
+        if above.rounded() == 0.0 {
+            text
+        } else {
+            format!("{} ± {}", text, above_text)
+        }
+    } else {
+        format!("{} (+{}/−{})", text, above_text, below_text)
+    }
+}
+
+/// Convert the value of a quantity datavalue to a claim value, with its uncertainty, rounding
+/// large numbers and converting its unit to the requested unit system
+pub fn extract_quantity(
+    value: &Value,
+    locale: &Locale,
+    unit_system: UnitSystem,
+) -> Option<ClaimValue> {
+    let mut amount = Amount::parse(value["amount"].as_str()?)?;
+    let mut lower = value["lowerBound"].as_str().and_then(Amount::parse);
+    let mut upper = value["upperBound"].as_str().and_then(Amount::parse);
+    let mut unit = match value["unit"].as_str()? {
+        "1" => None,
+        unit => Some(unit.strip_prefix(ENTITY_URL_PREFIX)?.parse::<u32>().ok()?),
+    };
+
+    if let Some((converted, factor, offset)) = unit.and_then(|u| conversion(u, unit_system)) {
+        amount = amount.convert(factor, offset);
+        lower = lower.map(|lower| Amount {
+            decimals: amount.decimals,
+            ..lower.convert(factor, offset)
+        });
+        upper = upper.map(|upper| Amount {
+            decimals: amount.decimals,
+            ..upper.convert(factor, offset)
+        });
+        unit = Some(converted);
+    }
+
+    let text = format_large_number(&amount, locale)
+        .unwrap_or_else(|| format_with_bounds(&amount, lower, upper, locale));
+    Some(match unit {
+        Some(unit) => ClaimValue::Quantity { amount: text, unit },
+        None => ClaimValue::Text(text),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dashmap::DashMap;
+    use serde_json::json;
+
+    /// Text and unit of a quantity, e.g. 100 metres
+    fn extract(value: Value, unit_system: UnitSystem) -> (String, Option<u32>) {
+        let locale = Locale::load("en", &DashMap::new());
+        match extract_quantity(&value, &locale, unit_system).unwrap() {
+            ClaimValue::Quantity { amount, unit } => (amount, Some(unit)),
+            ClaimValue::Text(text) => (text, None),
+            value => panic!("Unexpected claim value {:?}", value),
+        }
+    }
+
+    #[test]
+    fn rounds_large_numbers() {
+        let population = |amount: &str| {
+            extract(
+                json!({ "amount": amount, "unit": "1" }),
+                UnitSystem::Original,
+            )
+            .0
+        };
+        assert_eq!(population("+8804190"), "about 8.8 million");
+        assert_eq!(population("+2000000"), "2 million");
+        assert_eq!(population("+7900000000"), "7.9 billion");
+        assert_eq!(population("+872757"), "872,757");
+    }
+
+    #[test]
+    fn formats_bounds() {
+        let area = json!({
+            "amount": "+219.32",
+            "lowerBound": "+219.00",
+            "upperBound": "+219.64",
+            "unit": "http://www.wikidata.org/entity/Q712226",
+        });
+        assert_eq!(
+            extract(area, UnitSystem::Original),
+            ("219.32 ± 0.32".to_string(), Some(712226))
+        );
+        let asymmetric = json!({
+            "amount": "+12",
+            "lowerBound": "+11",
+            "upperBound": "+15",
+            "unit": "1",
+        });
+        assert_eq!(extract(asymmetric, UnitSystem::Original).0, "12 (+3/−1)");
+        let exact = json!({
+            "amount": "+12",
+            "lowerBound": "+12",
+            "upperBound": "+12",
+            "unit": "1",
+        });
+        assert_eq!(extract(exact, UnitSystem::Original).0, "12");
+    }
+
+    #[test]
+    fn converts_units_keeping_significant_digits() {
+        let height = json!({
+            "amount": "+100",
+            "unit": "http://www.wikidata.org/entity/Q11573",
+        });
+        assert_eq!(
+            extract(height, UnitSystem::Imperial),
+            ("328".to_string(), Some(3710))
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::fs::{remove_file, File};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::processing_error::ProcessingError;
+
+/// Name of the marker file that flags the output of an incomplete run
+const PARTIAL_MARKER: &str = "_PARTIAL";
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Handle SIGINT and SIGTERM by requesting a graceful shutdown.
+/// A second signal terminates the process immediately.
+pub fn install_handler() -> Result<(), ProcessingError> {
+    ctrlc::set_handler(|| {
+        if SHUTDOWN_REQUESTED.swap(true, Ordering::SeqCst) {
+            eprintln!("\nForced shutdown, output files are not flushed");
+            std::process::exit(130);
+        }
+        eprintln!("\nShutting down, flushing output files (press Ctrl-C again to force)");
+    })
+    .map_err(|e| ProcessingError::Other(format!("Failed to set signal handler: {}", e)))
+}
+
+/// Returns an error when a shutdown has been requested, to stop the parallel iterators
+pub fn check_shutdown() -> Result<(), ProcessingError> {
+    if SHUTDOWN_REQUESTED.load(Ordering::Relaxed) {
+        Err(ProcessingError::Interrupted)
+    } else {
+        Ok(())
+    }
+}
+
+/// Flag the output directory as incomplete, until `clear_partial` is called after a successful run
+pub fn mark_partial(output_dir: &str, reason: &str) -> Result<(), ProcessingError> {
+    let mut file = File::create(Path::new(output_dir).join(PARTIAL_MARKER))?;
+    writeln!(file, "{}", reason)?;
+    Ok(())
+}
+
+/// Whether the output directory is flagged as incomplete
+pub fn is_partial(output_dir: &str) -> bool {
+    Path::new(output_dir).join(PARTIAL_MARKER).exists()
+}
+
+/// Remove the incomplete marker from the output directory
+pub fn clear_partial(output_dir: &str) -> Result<(), ProcessingError> {
+    let path = Path::new(output_dir).join(PARTIAL_MARKER);
+    if path.exists() {
+        remove_file(path)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,147 @@
+// Names of the slots of a template, e.g. `{subject} was born in {object}.`
+/// Entity the sentence or question is about
+pub const SUBJECT: &str = "subject";
+/// Value, or values, of the property
+pub const OBJECT: &str = "object";
+/// Number of values of the property
+pub const COUNT: &str = "count";
+/// Label of the property, used by the templates of unknown and absent values
+pub const PROPERTY: &str = "property";
+/// Subject, object and possessive pronoun of the subject, e.g. she, her, her
+pub const PRONOUNS: [&str; 3] = ["pronoun", "object_pronoun", "possessive"];
+/// Prefix of the slots of qualifiers, e.g. `{qualifier.P580}` for the start time
+pub const QUALIFIER_PREFIX: &str = "qualifier.";
+
+/// Convert a template with positional `{}` markers to named slots, e.g. `{} was born in {}.` to
+/// `{subject} was born in {object}.`. Markers beyond the given names are kept as is.
+pub fn from_positional(template: &str, names: &[&str]) -> String {
+    let mut parts = template.split("{}");
+    let mut named = parts.next().unwrap_or_default().to_string();
+    for (index, part) in parts.enumerate() {
+        match names.get(index) {
+            Some(name) => {
+                named.push('{');
+                named.push_str(name);
+                named.push('}');
+            }
+            None => named.push_str("{}"),
+        }
+        named.push_str(part);
+    }
+    named
+}
+
+/// Keys of the qualifiers referenced by a template, e.g. P580 for `{qualifier.P580}`
+pub fn qualifier_slots(template: &str) -> impl Iterator<Item = &str> {
+    template
+        .split('{')
+        .skip(1)
+        .filter_map(|part| part.split_once('}'))
+        .filter_map(|(name, _)| name.strip_prefix(QUALIFIER_PREFIX))
+}
+
+/// Fill the named slots of a template, e.g. `{subject}`, using the values given by `slots`.
+/// Text between square brackets is an optional section, which is left out when any of its slots
+/// has no value, e.g. `[ from {qualifier.P580}]`. Returns `None` when a slot outside an optional
+/// section has no value.
+pub fn render(template: &str, slots: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+    render_section(&mut template.chars(), slots, false)
+}
+
+/// Render the template up to the end of the current section, or of the template itself
+fn render_section(
+    chars: &mut std::str::Chars,
+    slots: &dyn Fn(&str) -> Option<String>,
+    nested: bool,
+) -> Option<String> {
+    let mut text = String::new();
+    let mut complete = true;
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                if let Some(section) = render_section(chars, slots, true) {
+                    text.push_str(&section);
+                }
+            }
+            ']' if nested => break,
+            '{' => {
+                let rest = chars.as_str();
+                match rest.find('}') {
+                    // Unnamed or unterminated markers are kept as is
+                    Some(0) | None => text.push('{'),
+                    Some(end) => {
+                        match slots(&rest[..end]).filter(|value| !value.is_empty()) {
+                            Some(value) => text.push_str(&value),
+                            None => complete = false,
+                        }
+                        *chars = rest[end + 1..].chars();
+                    }
+                }
+            }
+            c => text.push(c),
+        }
+    }
+    complete.then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slots(slot: &str) -> Option<String> {
+        match slot {
+            SUBJECT => Some("Marie Curie".to_string()),
+            OBJECT => Some("Pierre Curie".to_string()),
+            "qualifier.P580" => Some("1895".to_string()),
+            "qualifier.P582" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn renders_optional_sections() {
+        assert_eq!(
+            render("{subject} married {object}[ in {qualifier.P580}].", &slots).as_deref(),
+            Some("Marie Curie married Pierre Curie in 1895.")
+        );
+        // Empty and missing values leave out the section, also when nested
+        assert_eq!(
+            render(
+                "{subject} married {object}[ until {qualifier.P582}].",
+                &slots
+            )
+            .as_deref(),
+            Some("Marie Curie married Pierre Curie.")
+        );
+        assert_eq!(
+            render(
+                "{subject} married {object}[ in {qualifier.P580}[ in {qualifier.P276}]].",
+                &slots
+            )
+            .as_deref(),
+            Some("Marie Curie married Pierre Curie in 1895.")
+        );
+    }
+
+    #[test]
+    fn requires_slots_outside_sections() {
+        assert_eq!(render("{subject} married {qualifier.P276}.", &slots), None);
+        assert_eq!(
+            render("{subject} {} {unterminated", &slots).as_deref(),
+            Some("Marie Curie {} {unterminated")
+        );
+    }
+
+    #[test]
+    fn converts_positional_markers() {
+        assert_eq!(
+            from_positional("{} married {} in {}.", &[SUBJECT, OBJECT]),
+            "{subject} married {object} in {}."
+        );
+        assert_eq!(
+            qualifier_slots("{subject}[ from {qualifier.P580}][ until {qualifier.P582}]")
+                .collect::<Vec<_>>(),
+            ["P580", "P582"]
+        );
+    }
+}
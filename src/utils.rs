@@ -1,94 +1,241 @@
-use chrono::NaiveDateTime;
-use dashmap::DashMap;
+use std::collections::HashMap;
 
-pub fn vec_to_and_string<T: AsRef<str>>(items: &Vec<T>, delimiter: &str) -> String {
-    let len = items.len();
+use crate::gender::Gender;
+use crate::locale::Locale;
+use crate::properties::Properties;
+use crate::template;
 
-    match len {
-        0 => String::new(),
-        1 => items[0].as_ref().to_string(),
-        _ => {
-            let mut joined = items[..len - 1]
+const FNV_PRIME: u64 = 0x100000001b3;
+/// Initial value of an FNV-1a hash
+pub const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+/// Continue an FNV-1a hash with the given bytes. Unlike the hasher of the standard library, its
+/// result is the same on every platform and Rust version.
+pub fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Join the items of a list using the separators of the locale, e.g. "A, B and C"
+pub fn vec_to_and_string<T: AsRef<str>>(items: &[T], locale: &Locale) -> String {
+    match items {
+        [] => String::new(),
+        [item] => item.as_ref().to_string(),
+        [first @ .., last] => format!(
+            "{} {} {}",
+            first
                 .iter()
                 .map(|s| s.as_ref()) // Convert to &str
                 .collect::<Vec<&str>>()
-                .join(", ");
-
-            joined.push_str(&format!(" {} {}", delimiter, items[len - 1].as_ref()));
-            joined
-        }
+                .join(&locale.list_separator),
+            locale.and_symbol,
+            last.as_ref()
+        ),
     }
 }
 
-pub fn generate_text<T: AsRef<str>>(
-    property_map: &DashMap<String, (String, String, String)>,
-    sentences: &mut Vec<String>,
-    questions: &mut Vec<String>,
-    and_symbol: &str,
+/// Sentence and question templates of a property with named slots. Templates of the classes of
+/// the subject, given in `scopes` with the most specific first, e.g. `P17@Q515`, take precedence
+/// over the templates of the property. Within each, templates for the gender of the subject, e.g.
+/// `P26#female`, or for several values, e.g. `P40#count` or `P26#plural`, take precedence, see
+/// `Properties::template_keys`. An empty column falls back to the next template. Templates with
+/// positional `{}` markers are converted: in a sentence, they refer to the subject and object, in
+/// a question to the subject. Keys with several rows pick one of them using the seed.
+pub fn property_templates(
+    properties: &Properties,
     prop_key: &str,
-    prop_label: &str,
-    prop_value: &Vec<T>,
-) {
-    let value = vec_to_and_string(prop_value, and_symbol);
-    match property_map.get(prop_key) {
-        Some(values) => {
-            let (_, sentence, question) = &*values;
-            let sentence = adjust_article(sentence, &value)
-                .replacen("{}", prop_label, 1)
-                .replacen("{}", &value, 1);
-            let question = question.replace("{}", &prop_label) + &format!(" [{}]", value);
-            if !sentence.is_empty() {
-                sentences.push(sentence);
-            }
-            if !question.is_ascii() {
-                questions.push(question);
-            }
-        }
-        None => {}
+    scopes: &[&str],
+    gender: Option<Gender>,
+    count: usize,
+    seed: u64,
+) -> Option<(String, String)> {
+    let templates: Vec<(&str, &str)> = scopes
+        .iter()
+        .chain(std::iter::once(&prop_key))
+        .flat_map(|key| Properties::template_keys(key, gender, count))
+        .filter_map(|key| properties.templates(&key, seed))
+        .collect();
+    if templates.is_empty() {
+        return None;
+    }
+    let first_non_empty = |column: for<'a> fn(&(&'a str, &'a str)) -> &'a str| {
+        templates
+            .iter()
+            .map(column)
+            .find(|template| !template.is_empty())
+            .unwrap_or_default()
     };
+    let sentence = first_non_empty(|&(sentence, _)| sentence);
+    let question = first_non_empty(|&(_, question)| question);
+    Some((
+        template::from_positional(sentence, &[template::SUBJECT, template::OBJECT]),
+        question.replace("{}", &format!("{{{}}}", template::SUBJECT)),
+    ))
 }
 
-// Helper function to format dates nicely
-pub fn format_date(date_str: &str, date_format: &str) -> String {
-    let cleaned_date = date_str.trim_start_matches('+'); // Remove leading '+'
-
-    match NaiveDateTime::parse_from_str(cleaned_date, "%Y-%m-%dT%H:%M:%SZ") {
-        Ok(date) => date.format(date_format).to_string(),
-        Err(_) => cleaned_date.replace("-00-00T00:00:00Z", ""), // In case only the year is published
-    }
+/// Value of a statement resolved to text, with the qualifiers that fill slots of the template
+pub struct ResolvedValue {
+    pub text: String,
+    /// Key of each qualifier referenced by the template, e.g. P580, mapped to its value
+    pub qualifiers: HashMap<String, String>,
 }
 
-pub fn format_coordinate(value: f64, is_latitude: bool) -> String {
-    let direction = match (is_latitude, value >= 0.0) {
-        (true, true) => "N",
-        (true, false) => "S",
-        (false, true) => "E",
-        (false, false) => "W",
+/// Generate the sentence and question of a property using its templates, each `None` when
+/// it could not be generated. When the sentence refers to qualifiers, a sentence is generated
+/// for each value, otherwise the values are listed in a single sentence. When `pronoun_subject`
+/// is set, a sentence about a man or woman that starts with the subject uses their pronoun
+/// instead of their label.
+pub fn generate_text(
+    templates: &(String, String),
+    locale: &Locale,
+    prop_label: &str,
+    values: &[ResolvedValue],
+    gender: Option<Gender>,
+    pronoun_subject: bool,
+) -> (Option<String>, Option<String>) {
+    let (sentence_template, question_template) = templates;
+    let texts: Vec<&str> = values.iter().map(|value| value.text.as_str()).collect();
+    let value = vec_to_and_string(&texts, locale);
+    let count = locale.format_number(&values.len().to_string());
+    let gender = gender.unwrap_or(Gender::Unknown);
+    let pronouns = locale.pronouns(gender);
+
+    let subject_slot = format!("{{{}}} ", template::SUBJECT);
+    let subject = match gender {
+        Gender::Male | Gender::Female
+            if pronoun_subject && sentence_template.starts_with(&subject_slot) =>
+        {
+            pronouns
+                .first()
+                .map_or(prop_label, String::as_str)
+                .to_string()
+        }
+        _ => prop_label.to_string(),
+    };
+    let fill = |template: &str, subject: &str, object: &str, value: Option<&ResolvedValue>| {
+        locale.grammar.render(template, &|slot: &str| match slot {
+            template::SUBJECT => Some(subject.to_string()),
+            template::OBJECT => Some(object.to_string()),
+            template::COUNT => Some(count.clone()),
+            slot => match slot.strip_prefix(template::QUALIFIER_PREFIX) {
+                Some(key) => value?.qualifiers.get(key).cloned(),
+                None => template::PRONOUNS
+                    .iter()
+                    .position(|pronoun| *pronoun == slot)
+                    .and_then(|index| pronouns.get(index).cloned()),
+            },
+        })
     };
 
-    let value = value.abs();
-    let degrees = value.trunc() as i64;
-    let minutes = ((value - degrees as f64) * 60.0).trunc() as i64;
-    let seconds = ((value - degrees as f64 - minutes as f64 / 60.0) * 3600.0).round() as i64;
+    let sentence = if template::qualifier_slots(sentence_template)
+        .next()
+        .is_some()
+    {
+        let sentences: Vec<String> = values
+            .iter()
+            .filter_map(|value| fill(sentence_template, &subject, &value.text, Some(value)))
+            .collect();
+        Some(sentences.join("\n"))
+    } else {
+        fill(
+            sentence_template,
+            &subject,
+            &value,
+            values.first().filter(|_| values.len() == 1),
+        )
+    };
+    let question = fill(
+        question_template,
+        prop_label,
+        &value,
+        values.first().filter(|_| values.len() == 1),
+    )
+    .map(|question| question + &format!(" [{}]", value));
+    (
+        sentence.filter(|sentence| !sentence.is_empty()),
+        question.filter(|_| !question_template.is_empty()),
+    )
+}
 
-    format!("{}°{}'{}\"{}", degrees, minutes, seconds, direction)
+/// Generate the sentence and question of a property with an unknown value (`somevalue`) or
+/// without a value (`novalue`). A property specific template such as `P40#novalue` is preferred
+/// over the generic template of the snak type, which may also refer to the label of the
+/// property, e.g. `{subject} has no {property}.`. The question of the property is answered
+/// with the answer of the generic template, e.g. "unknown" or "none".
+pub fn generate_snak_type_text(
+    properties: &Properties,
+    snak_type: &str,
+    prop_key: &str,
+    prop_label: &str,
+    seed: u64,
+) -> (Option<String>, Option<String>) {
+    let Some((generic_sentence, answer)) = properties.templates(snak_type, seed) else {
+        return (None, None);
+    };
+    let property = properties.templates(prop_key, seed);
+    let specific = properties.templates(&format!("{}#{}", prop_key, snak_type), seed);
+    let grammar = properties.locale.grammar;
+    let property_label = properties
+        .property_map
+        .get(prop_key)
+        .map(|property| property.0.clone());
+    let slots = |slot: &str| match slot {
+        template::SUBJECT => Some(prop_label.to_string()),
+        template::PROPERTY => property_label.clone(),
+        _ => None,
+    };
+
+    let sentence = match (&specific, &property) {
+        (Some(specific), _) if !specific.0.is_empty() => grammar.render(
+            &template::from_positional(specific.0, &[template::SUBJECT]),
+            &slots,
+        ),
+        (_, Some(_)) => grammar.render(
+            &template::from_positional(generic_sentence, &[template::SUBJECT, template::PROPERTY]),
+            &slots,
+        ),
+        _ => None,
+    };
+    let question_template = specific
+        .as_ref()
+        .map(|specific| specific.1)
+        .filter(|question| !question.is_empty())
+        .or(property.as_ref().map(|property| property.1))
+        .filter(|question| !question.is_empty());
+    let question = question_template
+        .and_then(|question| {
+            grammar.render(
+                &question.replace("{}", &format!("{{{}}}", template::SUBJECT)),
+                &slots,
+            )
+        })
+        .map(|question| question + &format!(" [{}]", answer));
+    (sentence.filter(|sentence| !sentence.is_empty()), question)
 }
 
-pub fn format_coordinates(lat: &str, lon: &str, alt: Option<&str>) -> String {
-    let lat_f: f64 = lat.parse().unwrap();
-    let lon_f: f64 = lon.parse().unwrap();
+/// Base URL of pages on Wikimedia Commons
+const COMMONS_WIKI_URL: &str = "https://commons.wikimedia.org/wiki/";
 
-    let coords = format!(
-        "{}, {}",
-        format_coordinate(lat_f, true),
-        format_coordinate(lon_f, false)
-    );
+/// URL of a page on Wikimedia Commons, e.g. a media file such as `File:Anne Frank.jpg` or a data
+/// page such as `Data:Berlin.map`
+pub fn commons_url(page: &str) -> String {
+    format!("{}{}", COMMONS_WIKI_URL, page.trim().replace(' ', "_"))
+}
 
-    if let Some(altitude) = alt {
-        let alt_f: f64 = altitude.parse().unwrap();
-        format!("{} ({}m)", coords, alt_f.round())
-    } else {
-        coords
+/// Replace each run of whitespace, including newlines, by a single space
+pub fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Capitalize the first letter of a string, e.g. She for she
+pub fn uppercase_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
     }
 }
 
@@ -100,15 +247,3 @@ pub fn lowercase_first(s: &str) -> String {
         None => String::new(),
     }
 }
-
-/// Adjust the English article "a" to "an" if the next word starts with a vowel.
-pub fn adjust_article(first: &str, second: &str) -> String {
-    if first.ends_with(" a {}.") && second.chars().next().map_or(false, |c| "aeiou".contains(c)) {
-        let mut adjusted = first.to_string();
-        adjusted.truncate(first.len() - 5); // Remove " a {}."
-        adjusted.push_str(" an {}.");
-        adjusted
-    } else {
-        first.to_string()
-    }
-}